@@ -4,7 +4,7 @@ use crate::model::*;
 use codee::string::JsonSerdeCodec;
 use js_sys::{JSON, Promise};
 use lazy_regex::regex;
-use leptos::{prelude::*, task::spawn_local};
+use leptos::{prelude::*, server_fn::codec::Json, task::spawn_local};
 // https://carloskiki.github.io/icondata/
 use leptos_icons::Icon;
 use leptos_meta::{Link, MetaTags, Script, Stylesheet, Title, provide_meta_context};
@@ -78,6 +78,9 @@ pub fn App() -> impl IntoView {
                     <Route path=StaticSegment("") view=HomePage />
                     <Route path=path!("/:timer_id/timer") view=TimerPage />
                     <Route path=path!("/:timer_id/settings") view=SettingsPage />
+                    <Route path=path!("/:timer_id/players") view=PlayersPage />
+                    <Route path=path!("/:timer_id/deal") view=DealPage />
+                    <Route path=path!("/structures") view=StructuresPage />
                 </Routes>
             </main>
             <About />
@@ -189,9 +192,12 @@ fn HomePage() -> impl IntoView {
     );
     let name_signal = RwSignal::<Result<String, String>>::new(Err("Required".to_string()));
     let break_name_signal = RwSignal::<String>::new(String::new());
-    let onsubmit = move |_| {
+    let generate = RwSignal::new(false);
+    let generator_settings = RwSignal::new(GeneratorSettings::default());
+    let generate_error = RwSignal::<Option<String>>::new(None);
+    let onsubmit = move |ev: leptos::ev::SubmitEvent| {
         let break_name = break_name_signal.get();
-        set_timers.write().push(TimerRef {
+        let timer = TimerRef {
             id: Uuid::new_v4(),
             name: name_signal.get().unwrap(),
             break_name: if break_name.is_empty() {
@@ -199,7 +205,38 @@ fn HomePage() -> impl IntoView {
             } else {
                 Some(break_name)
             },
-        });
+        };
+        if generate.get_untracked() {
+            // start the timer with a generated structure and go straight to it,
+            // only keeping it if it got that far
+            ev.prevent_default();
+            let settings = generator_settings.get_untracked();
+            spawn_local(async move {
+                let result = match generate_structure(settings).await {
+                    Ok(structure) => {
+                        create_tournament(timer.id, "Generated".to_string(), Some(structure), None)
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(_) => {
+                        set_timers.write().push(timer.clone());
+                        use_navigate()(
+                            &format!(
+                                "/{}/timer?{}",
+                                timer.id,
+                                timer_query(&timer.name, timer.break_name.as_deref())
+                            ),
+                            NavigateOptions::default(),
+                        )
+                    }
+                    Err(e) => generate_error.set(Some(e.to_string())),
+                }
+            });
+        } else {
+            set_timers.write().push(timer);
+        }
     };
 
     let link_signal = RwSignal::<Result<String, String>>::new(Err("Required".to_string()));
@@ -303,11 +340,108 @@ fn HomePage() -> impl IntoView {
                     prop:value=move || break_name_signal.get()
                 />
             </div>
+            <div class="form_group">
+                <label>
+                    <input
+                        type="checkbox"
+                        prop:checked=move || generate.get()
+                        on:change:target=move |ev| generate.set(ev.target().checked())
+                    />
+                    "Start it with a generated structure"
+                </label>
+            </div>
+            <Show when=move || generate.get()>
+                <GeneratorForm settings=generator_settings />
+            </Show>
             <button disabled=move || name_signal.get().is_err()>"Create"</button>
+            {move || {
+                generate_error.get().map(|e| view! { <div class="error-message">{e}</div> })
+            }}
         </form>
     }
 }
 
+/// The settings for the structure generator
+#[component]
+fn GeneratorForm(settings: RwSignal<GeneratorSettings>) -> impl IntoView {
+    // a number field that writes straight into the settings
+    let field = move |label: &'static str,
+                      get: fn(&GeneratorSettings) -> String,
+                      set: fn(&mut GeneratorSettings, &str)| {
+        view! {
+            <div class="form_group">
+                <label>
+                    {label}
+                    " "
+                    <input
+                        type="number"
+                        min="0"
+                        class="level-number"
+                        prop:value=move || settings.with(get)
+                        on:change:target=move |ev| {
+                            let v = ev.target().value();
+                            settings.update(|s| set(s, v.trim()));
+                        }
+                    />
+                </label>
+            </div>
+        }
+    };
+    view! {
+        <div class="form_group">
+            <label>
+                "Game "
+                <input
+                    type="text"
+                    class="input-field"
+                    prop:value=move || settings.with(|s| s.game.clone())
+                    on:change:target=move |ev| settings.update(|s| s.game = ev.target().value())
+                />
+            </label>
+        </div>
+        {field(
+            "Starting stack",
+            |s| s.starting_stack.to_string(),
+            |s, v| s.starting_stack = v.parse().unwrap_or(s.starting_stack),
+        )}
+        {field(
+            "Players",
+            |s| s.players.to_string(),
+            |s, v| s.players = v.parse().unwrap_or(s.players),
+        )}
+        {field(
+            "Length (hours)",
+            |s| s.hours.to_string(),
+            |s, v| s.hours = v.parse().unwrap_or(s.hours),
+        )}
+        {field(
+            "Level length (minutes)",
+            |s| s.level_minutes.to_string(),
+            |s, v| s.level_minutes = v.parse().unwrap_or(s.level_minutes),
+        )}
+        {field(
+            "Smallest chip",
+            |s| s.smallest_chip.to_string(),
+            |s, v| s.smallest_chip = v.parse().unwrap_or(s.smallest_chip),
+        )}
+        {field(
+            "Break every (levels, blank for none)",
+            |s| s.break_every.map(|v| v.to_string()).unwrap_or_default(),
+            |s, v| s.break_every = v.parse().ok(),
+        )}
+        {field(
+            "Break length (minutes)",
+            |s| s.break_minutes.to_string(),
+            |s, v| s.break_minutes = v.parse().unwrap_or(s.break_minutes),
+        )}
+        {field(
+            "Antes from level (blank for none)",
+            |s| s.antes_from.map(|v| v.to_string()).unwrap_or_default(),
+            |s, v| s.antes_from = v.parse().ok(),
+        )}
+    }
+}
+
 #[derive(Params, PartialEq, Clone, Debug)]
 struct RawTimerPageParams {
     timer_id: Option<Uuid>,
@@ -482,6 +616,8 @@ fn TimerComp(
         None => format!("/{}/ws", timer_id),
     };
     let settable_state = RwSignal::new(TimerCompState::Loading);
+    // the last seat changes, until someone says they're done
+    let seating = RwSignal::<Option<SeatingChange>>::new(None);
     let socket = use_websocket_with_options::<Command, DeviceMessage, JsonSerdeCodec, _, _>(
         &ws_path,
         UseWebSocketOptions::default()
@@ -510,11 +646,17 @@ fn TimerComp(
                     blink_screen();
                     beep();
                 }
+                DeviceMessage::Seating(change) => {
+                    seating.set(Some(change));
+                }
             };
         }
     });
     let structures = LocalResource::new(|| structure_names());
     let selected_structure = RwSignal::new("Nightly NLHE".to_string());
+    // blank to start straight away
+    let start_time = RwSignal::new(String::new());
+    let start_error = RwSignal::<Option<String>>::new(None);
 
     view! {
         {{
@@ -533,17 +675,32 @@ fn TimerComp(
 
                             <p>
                                 <h1>"No tournament running"</h1>
+                                <a class="links" rel="external" href=format!("/{timer_id}/results.csv")>
+                                    "Download the Last Results"
+                                </a>
                                 <form
                                     class="form"
                                     on:submit=move |ev| {
                                         ev.prevent_default();
+                                        let starts_at = match parse_start_time(&start_time.get_untracked()) {
+                                            Ok(starts_at) => starts_at,
+                                            Err(e) => {
+                                                start_error.set(Some(e));
+                                                return;
+                                            }
+                                        };
+                                        start_error.set(None);
                                         spawn_local(async move {
-                                            create_tournament(
+                                            if let Err(e) = create_tournament(
                                                     timer_id,
                                                     selected_structure.get_untracked(),
+                                                    None,
+                                                    starts_at,
                                                 )
                                                 .await
-                                                .unwrap();
+                                            {
+                                                start_error.set(Some(e.to_string()));
+                                            }
                                         });
                                     }
                                 >
@@ -577,7 +734,26 @@ fn TimerComp(
 
                                         </select>
                                     </div>
+                                    <div class="form-group">
+                                        <label for="start_time">"Start at (optional):"</label>
+                                        <input
+                                            type="datetime-local"
+                                            id="start_time"
+                                            prop:value=start_time
+                                            on:input:target=move |ev| start_time.set(ev.target().value())
+                                        />
+                                    </div>
+                                    {move || {
+                                        start_error.get().map(|e| view! { <div class="error-message">{e}</div> })
+                                    }}
                                     <button type="submit">Start</button>
+                                    " "
+                                    <a
+                                        class="links"
+                                        href=format!("/structures?timer_id={timer_id}&{qr_query}")
+                                    >
+                                        "Custom Structure"
+                                    </a>
                                 </form>
                             </p>
                             <div class="qr-code-section">
@@ -607,19 +783,120 @@ fn TimerComp(
                             <div class="timer-main-content">
                                 <div class="timer-info-section">
                                     <div class="level">
-                                        "Level " {state.level} ": " {state.cur.game().to_string()}
+                                        "Level " {state.level.number()} ": " {state.cur.game_label()}
                                     </div>
                                     <div class="cur-level">{cur_display_string}</div>
+                                    {match &state.cur {
+                                        Level::Rotation { rotate, .. } => {
+                                            let playing = state.cur.rotation_game(state.rotation);
+                                            let next = state.cur.rotation_game(state.rotation + 1);
+                                            let send = socket.send.clone();
+                                            Some(
+                                                view! {
+                                                    <div class="rotation">
+                                                        <div class="rotation-game">
+                                                            "Now Playing: " {playing.map(|g| g.to_string())}
+                                                        </div>
+                                                        <div>
+                                                            "Next Game: " {next.map(|g| g.to_string())}
+                                                            " (changes " {rotate.to_string()} ") "
+                                                            <button on:click=move |_| send(
+                                                                &Command::NextGame,
+                                                            )>"Next Game"</button>
+                                                        </div>
+                                                    </div>
+                                                },
+                                            )
+                                        }
+                                        _ => None,
+                                    }}
+                                    {state
+                                        .color_up
+                                        .map(|color_up| {
+                                            view! { <div class="color-up">{color_up.to_string()}</div> }
+                                        })}
+                                    {state
+                                        .starts_in
+                                        .map(|starts_in| {
+                                            view! {
+                                                <div class="starts-in">
+                                                    "Starts in " <Clock state=starts_in />
+                                                </div>
+                                            }
+                                        })}
                                     <div class="clock">
                                         <Clock state=state.clock />
                                     </div>
                                     <div class="next-level">"Next Level: " {next_display_string}</div>
+                                    {(state.entrants > 0)
+                                        .then(|| {
+                                            view! {
+                                                <div class="players">
+                                                    "Players: " {state.remaining} " / "
+                                                    {state.entrants}
+                                                    {state.average_stack
+                                                        .map(|avg| format!("  Average Stack: {avg}"))}
+                                                </div>
+                                            }
+                                        })}
+                                    {state.prize_pool.map(|pool| view! { <PrizePoolBox pool /> })}
+                                    {move || {
+                                        seating
+                                            .get()
+                                            .map(|change| {
+                                                view! {
+                                                    <SeatingBox
+                                                        change
+                                                        on_done=move || seating.set(None)
+                                                    />
+                                                }
+                                            })
+                                    }}
+                                    {state
+                                        .last_rebuy_level
+                                        .then(|| {
+                                            view! { <div class="players">"Last level for rebuys"</div> }
+                                        })}
+                                    <div>
+                                        <a
+                                            class="links"
+                                            href=format!("/{timer_id}/players?{qr_query}")
+                                        >
+                                            "Players"
+                                        </a>
+                                    </div>
                                     <p>
                                     <div><NotificationBox timer_id=timer_id subscribed=subscribed device_id=device_id /></div>
                                     <div><WakeLockBox /></div>
                                     </p>
-                                    {match state.clock {
-                                        ClockState::Paused { .. } => {
+                                    {match (state.hand_for_hand, state.clock) {
+                                        (Some(hand_for_hand), _) => {
+                                            view! {
+                                                <div class="hand-for-hand">
+                                                    <div>"Hand for Hand: Hand " {hand_for_hand.hand}</div>
+                                                    {hand_for_hand
+                                                        .waiting_on()
+                                                        .into_iter()
+                                                        .map(|table| {
+                                                            let send = socket.send.clone();
+                                                            view! {
+                                                                <button on:click=move |_| send(
+                                                                    &Command::HandComplete(table),
+                                                                )>{format!("Table {table} Done")}</button>
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                    <div>
+                                                        <button on:click={
+                                                            let send = socket.send.clone();
+                                                            move |_| send(&Command::StopHandForHand)
+                                                        }>"Stop Hand for Hand"</button>
+                                                    </div>
+                                                </div>
+                                            }
+                                                .into_any()
+                                        }
+                                        (None, ClockState::Paused { .. }) => {
                                             view! {
                                                 <button on:click={
                                                     let send = socket.send.clone();
@@ -628,12 +905,17 @@ fn TimerComp(
                                             }
                                                 .into_any()
                                         }
-                                        ClockState::Running { .. } => {
+                                        (None, ClockState::Running { .. }) => {
                                             view! {
                                                 <button on:click={
                                                     let send = socket.send.clone();
                                                     move |_| send(&Command::Pause)
                                                 }>Pause</button>
+                                                " "
+                                                <button on:click={
+                                                    let send = socket.send.clone();
+                                                    move |_| send(&Command::StartHandForHand)
+                                                }>"Hand for Hand"</button>
                                             }
                                                 .into_any()
                                         }
@@ -652,6 +934,81 @@ fn TimerComp(
     }
 }
 
+/// The tables to break and the players that have to move
+#[component]
+fn SeatingBox(change: SeatingChange, on_done: impl Fn() + 'static) -> impl IntoView {
+    view! {
+        <div class="seating">
+            {change
+                .broken_tables
+                .into_iter()
+                .map(|table| view! { <div>{format!("Break table {table}")}</div> })
+                .collect_view()}
+            {change
+                .moves
+                .into_iter()
+                .map(|m| view! { <div>{m.to_string()}</div> })
+                .collect_view()}
+            <button on:click=move |_| on_done()>"Done"</button>
+        </div>
+    }
+}
+
+/// The prize pool and who gets what
+#[component]
+fn PrizePoolBox(pool: PrizePool) -> impl IntoView {
+    view! {
+        <div class="prize-pool">
+            <div>"Prize Pool: " {pool.collected - pool.bounties - pool.rake}</div>
+            {(pool.bounties > 0).then(|| view! { <div>"Bounties: " {pool.bounties}</div> })}
+            {pool
+                .payouts
+                .into_iter()
+                .map(|payout| {
+                    view! {
+                        <div>
+                            {format!("{}: {}", ordinal(payout.place), payout.amount)}
+                            {payout.player.map(|name| format!(" ({name})"))}
+                        </div>
+                    }
+                })
+                .collect_view()}
+        </div>
+    }
+}
+
+/// 1st, 2nd, 3rd, etc.
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+/// the local time from a datetime-local input, `None` when it's blank. A
+/// time that the clocks skip or go through twice is an error
+fn parse_start_time(value: &str) -> Result<Option<DateTime>, String> {
+    use chrono::{LocalResult, TimeZone};
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    let naive = chrono::NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%dT%H:%M")
+        .map_err(|_| format!("\"{value}\" isn't a start time"))?;
+    match chrono::Local.from_local_datetime(&naive) {
+        LocalResult::Single(time) => Ok(Some(time)),
+        LocalResult::Ambiguous(_, _) => Err(format!(
+            "{naive} happens twice when the clocks go back, pick a time outside that hour"
+        )),
+        LocalResult::None => Err(format!(
+            "{naive} doesn't happen, the clocks skip it when they go forward"
+        )),
+    }
+}
+
 #[component]
 fn SettingsButton(
     timer_id: Uuid,
@@ -699,21 +1056,26 @@ pub async fn current_state(
     timer_id: Uuid,
 ) -> Result<TimerCompState, ServerFnError> {
     use crate::timers::Timer;
-    Ok(Timer::get(timer_id).to_timer_comp_state(&device_id))
+    Ok(Timer::open(timer_id)?.to_timer_comp_state(&device_id))
 }
 
-#[server]
+/// Start a tournament using a named structure, or a one off `structure` that is
+/// only used by this tournament. With `starts_at` the clock waits and starts
+/// by itself at that time.
+#[server(input = Json)]
 pub async fn create_tournament(
     timer_id: Uuid,
     structure_name: String,
+    structure: Option<Structure>,
+    starts_at: Option<DateTime>,
 ) -> Result<(), ServerFnError> {
     use crate::timers::Timer;
-    let mut timer = Timer::get_mut(timer_id);
+    let mut timer = Timer::open_mut(timer_id)?;
     if timer.tournament.is_some() {
         return Ok(());
     }
     info!("Creating tournament {timer_id}");
-    timer.make_tournament(structure_name)
+    timer.make_tournament(structure_name, structure, starts_at)
 }
 
 #[component]
@@ -872,7 +1234,7 @@ pub async fn add_subscription(
     use crate::backend::Subscription;
     use crate::timers::Timer;
     let subscription = serde_json::from_str::<Subscription>(&subscription)?;
-    let mut t = Timer::get_mut(timer_id);
+    let mut t = Timer::open_mut(timer_id)?;
     info!("{device_id} subscription: {subscription:?}");
     t.subscribe(device_id, subscription);
     Ok(())
@@ -888,7 +1250,7 @@ async fn stop_notifications(device_id: Uuid, timer_id: Uuid) -> Result<(), Serve
 #[server]
 pub async fn remove_subscription(device_id: Uuid, timer_id: Uuid) -> Result<(), ServerFnError> {
     use crate::timers::Timer;
-    let mut t = Timer::get_mut(timer_id);
+    let mut t = Timer::open_mut(timer_id)?;
     t.unsubscribe(device_id);
     Ok(())
 }
@@ -938,11 +1300,58 @@ pub fn blink_screen() {
     };
 }
 
+/// The level lengths that are different from the structure, one rule to a line
 #[component]
-fn InputOptionalDuration(
-    name: String,
-    signal: RwSignal<Result<Option<Duration>, String>>,
-) -> impl IntoView {
+fn InputDurations(text: RwSignal<String>) -> impl IntoView {
+    view! {
+        <div class="form-group">
+            <label for="durations">"Level Lengths"</label>
+            <textarea
+                id="durations"
+                rows="3"
+                placeholder="e.g. 10+: 12 or breaks: 15"
+                prop:value=move || text.get()
+                on:input:target=move |ev| text.set(ev.target().value())
+            ></textarea>
+            <div>
+                "One per line: the levels (3, 3-5, 10+ or all) or breaks, then the minutes"
+            </div>
+            {move || {
+                DurationOverrides::parse(&text.get())
+                    .err()
+                    .map(|e| view! { <div class="error-message">{e}</div> })
+            }}
+        </div>
+    }
+}
+
+/// The warnings given before levels end, one to a line
+#[component]
+fn InputWarnings(text: RwSignal<String>) -> impl IntoView {
+    view! {
+        <div class="form-group">
+            <label for="warnings">"Warnings"</label>
+            <textarea
+                id="warnings"
+                rows="3"
+                placeholder="e.g. 5m push: Five minutes left"
+                prop:value=move || text.get()
+                on:input:target=move |ev| text.set(ev.target().value())
+            ></textarea>
+            <div>
+                "One per line: the time before the level ends (5m, 30s or 1m30s), then breaks to only warn before breaks end, sound to beep, push to send a notification, and after a ':' the message"
+            </div>
+            {move || {
+                Warnings::parse(&text.get())
+                    .err()
+                    .map(|e| view! { <div class="error-message">{e}</div> })
+            }}
+        </div>
+    }
+}
+
+#[component]
+fn InputOptionalNumber(name: String, signal: RwSignal<Result<Option<u32>, String>>) -> impl IntoView {
     view! {
         <div class="form-group">
             <label for=name.clone()>{name.clone()}</label>
@@ -952,24 +1361,22 @@ fn InputOptionalDuration(
                 name=name
                 on:input:target=move |evt| {
                     let v = evt.target().value();
-                    if v.len() == 0 {
+                    if v.trim().is_empty() {
                         signal.set(Ok(None));
                     } else {
-                        match v.parse::<i64>() {
-                            Ok(v) => signal.set(Ok(Some(Duration::minutes(v)))),
+                        match v.trim().parse::<u32>() {
+                            Ok(v) => signal.set(Ok(Some(v))),
                             Err(e) => signal.set(Err(e.to_string())),
                         }
                     }
                 }
                 prop:value=move || {
                     match signal.get() {
-                        Ok(None) => "".to_string(),
-                        Ok(Some(duration)) => duration.num_minutes().to_string(),
-                        Err(_) => "".to_string(),
+                        Ok(Some(v)) => v.to_string(),
+                        _ => "".to_string(),
                     }
                 }
             />
-            " min"
             {move || {
                 if let Err(s) = signal.get() {
                     Some(view! { <div class="error-message">{s}</div> })
@@ -981,14 +1388,276 @@ fn InputOptionalDuration(
     }
 }
 
-// TODO - change current level's time
+/// Turn one kind of entry on or off, and set its price, chips and last level
+#[component]
+fn EntryRuleInput(kind: EntryKind, entries: RwSignal<EntryRules>) -> impl IntoView {
+    let rule = move || entries.with(|e| e.get(kind).cloned());
+    let field = move |label: &'static str,
+                      get: fn(&EntryRule) -> Option<u32>,
+                      set: fn(&mut EntryRule, Option<u32>)| {
+        view! {
+            <label>
+                {label}
+                " "
+                <input
+                    type="number"
+                    min="0"
+                    class="level-number"
+                    prop:value=move || {
+                        rule().and_then(|r| get(&r)).map(|v| v.to_string()).unwrap_or_default()
+                    }
+                    on:change:target=move |ev| {
+                        let v = parse_number(&ev.target().value());
+                        entries.update(|e| {
+                            if let Some(rule) = e.get_mut(kind) {
+                                set(rule, v)
+                            }
+                        });
+                    }
+                />
+            </label>
+        }
+    };
+    view! {
+        <div class="form-group">
+            <label>
+                <input
+                    type="checkbox"
+                    prop:checked=move || rule().is_some()
+                    on:change:target=move |ev| {
+                        let on = ev.target().checked();
+                        entries.update(|e| *e.get_mut(kind) = on.then(EntryRule::default));
+                    }
+                />
+                {kind.name()}
+            </label>
+            <Show when=move || rule().is_some()>
+                <div>
+                    {field("Price", |r| Some(r.price), |r, v| r.price = v.unwrap_or_default())}
+                    {field("Chips", |r| Some(r.chips), |r, v| r.chips = v.unwrap_or_default())}
+                    {field(
+                        "Last Level",
+                        |r| r.last_level.map(|l| l as u32),
+                        |r, v| r.last_level = v.map(|l| l as usize),
+                    )}
+                </div>
+            </Show>
+        </div>
+    }
+}
+
+/// Turn bounties on or off, and set how much they are and how they're paid
+#[component]
+fn BountyInput(bounty: RwSignal<Option<BountyConfig>>) -> impl IntoView {
+    let kinds = [
+        BountyKind::Fixed,
+        BountyKind::Progressive { cash_percent: 50 },
+    ];
+    let number = move |label: &'static str,
+                       get: fn(&BountyConfig) -> Option<u32>,
+                       set: fn(&mut BountyConfig, u32)| {
+        view! {
+            <label>
+                {label}
+                " "
+                <input
+                    type="number"
+                    min="0"
+                    class="level-number"
+                    prop:value=move || {
+                        bounty.with(|b| b.as_ref().and_then(get)).map(|v| v.to_string()).unwrap_or_default()
+                    }
+                    on:change:target=move |ev| {
+                        let v = parse_number(&ev.target().value()).unwrap_or_default();
+                        bounty.update(|b| {
+                            if let Some(b) = b {
+                                set(b, v)
+                            }
+                        });
+                    }
+                />
+            </label>
+        }
+    };
+    view! {
+        <div class="form-group">
+            <label>
+                <input
+                    type="checkbox"
+                    prop:checked=move || bounty.with(|b| b.is_some())
+                    on:change:target=move |ev| {
+                        let on = ev.target().checked();
+                        bounty.set(on.then(BountyConfig::default));
+                    }
+                />
+                "Bounties"
+            </label>
+            <Show when=move || bounty.with(|b| b.is_some())>
+                <div>
+                    {number("Bounty", |b| Some(b.amount), |b, v| b.amount = v)}
+                    <select on:change:target=move |ev| {
+                        let name = ev.target().value();
+                        if let Some(kind) = kinds.iter().find(|k| k.name() == name) {
+                            bounty.update(|b| {
+                                if let Some(b) = b {
+                                    b.kind = *kind
+                                }
+                            });
+                        }
+                    }>
+                        {kinds
+                            .iter()
+                            .map(|k| {
+                                let name = k.name();
+                                view! {
+                                    <option
+                                        value=name
+                                        selected=move || {
+                                            bounty.with(|b| b.as_ref().is_some_and(|b| b.kind.name() == name))
+                                        }
+                                    >
+                                        {name}
+                                    </option>
+                                }
+                            })
+                            .collect_view()}
+                    </select>
+                    <Show when=move || {
+                        bounty.with(|b| matches!(b, Some(BountyConfig { kind: BountyKind::Progressive { .. }, .. })))
+                    }>
+                        {number(
+                            "Cash %",
+                            |b| match b.kind {
+                                BountyKind::Progressive { cash_percent } => Some(cash_percent),
+                                BountyKind::Fixed => None,
+                            },
+                            |b, v| b.kind = BountyKind::Progressive { cash_percent: v },
+                        )}
+                    </Show>
+                </div>
+            </Show>
+        </div>
+    }
+}
 
+/// The rake and payout table
 #[component]
-fn SettingsPage() -> impl IntoView {
-    let duration_override_signal =
-        RwSignal::<Result<Option<Duration>, String>>::new(Err("Required".to_string()));
+fn PayoutInput(payouts: RwSignal<PayoutConfig>) -> impl IntoView {
+    let field = move |label: &'static str,
+                      get: fn(&PayoutConfig) -> u32,
+                      set: fn(&mut PayoutConfig, u32)| {
+        view! {
+            <label>
+                {label}
+                " "
+                <input
+                    type="number"
+                    min="0"
+                    class="level-number"
+                    prop:value=move || payouts.with(get).to_string()
+                    on:change:target=move |ev| {
+                        let v = parse_number(&ev.target().value()).unwrap_or_default();
+                        payouts.update(|p| set(p, v));
+                    }
+                />
+            </label>
+        }
+    };
+    let tables = [
+        PayoutTable::Standard,
+        PayoutTable::Flat,
+        PayoutTable::WinnerTakesAll,
+        PayoutTable::Custom(vec![]),
+    ];
+    view! {
+        <div class="form-group">
+            <label>"Payouts"</label>
+            <div>
+                {field("Rake %", |p| p.rake_percent, |p, v| p.rake_percent = v)}
+                {field("Fixed Rake", |p| p.rake_fixed, |p, v| p.rake_fixed = v)}
+                {field("Round To", |p| p.round_to, |p, v| p.round_to = v.max(1))}
+            </div>
+            <select on:change:target=move |ev| {
+                let name = ev.target().value();
+                if let Some(table) = tables.iter().find(|t| t.name() == name) {
+                    payouts.update(|p| p.table = table.clone());
+                }
+            }>
+                {tables
+                    .iter()
+                    .map(|t| {
+                        let name = t.name();
+                        view! {
+                            <option
+                                value=name
+                                selected=move || payouts.with(|p| p.table.name() == name)
+                            >
+                                {name}
+                            </option>
+                        }
+                    })
+                    .collect_view()}
+            </select>
+            <Show when=move || matches!(payouts.with(|p| p.table.clone()), PayoutTable::Custom(_))>
+                <label>
+                    "Percent for each place, e.g. 50, 30, 20"
+                    <input
+                        type="text"
+                        prop:value=move || {
+                            match payouts.with(|p| p.table.clone()) {
+                                PayoutTable::Custom(percentages) => {
+                                    percentages
+                                        .iter()
+                                        .map(|p| p.to_string())
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                }
+                                _ => String::new(),
+                            }
+                        }
+                        on:change:target=move |ev| {
+                            let percentages = ev
+                                .target()
+                                .value()
+                                .split(',')
+                                .filter_map(parse_number)
+                                .collect();
+                            payouts.update(|p| p.table = PayoutTable::Custom(percentages));
+                        }
+                    />
+                </label>
+            </Show>
+        </div>
+    }
+}
 
-    let old_settings: Resource<Result<Option<Duration>, ServerFnError>> = Resource::new(
+/// parse a time left like "12" (minutes) or "12:30"
+fn parse_time(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    match s.split_once(':') {
+        Some((min, sec)) => {
+            let min: u32 = min.trim().parse().ok()?;
+            let sec: u32 = sec.trim().parse().ok()?;
+            (sec < 60).then(|| Duration::minutes(min.into()) + Duration::seconds(sec.into()))
+        }
+        None => s.parse::<u32>().ok().map(|m| Duration::minutes(m.into())),
+    }
+}
+
+#[component]
+fn SettingsPage() -> impl IntoView {
+    let durations_text = RwSignal::new(String::new());
+    let warnings_text = RwSignal::new(String::new());
+
+    let starting_stack_signal = RwSignal::<Result<Option<u32>, String>>::new(Ok(None));
+    let table_size_signal = RwSignal::<Result<Option<u32>, String>>::new(Ok(None));
+    let bounty_signal = RwSignal::<Option<BountyConfig>>::new(None);
+    let entries_signal = RwSignal::new(EntryRules::default());
+    let payouts_signal = RwSignal::new(PayoutConfig::default());
+    let save_error = RwSignal::<Option<String>>::new(None);
+    let time_left = RwSignal::new(String::new());
+
+    let old_settings: Resource<Result<TournamentConfig, ServerFnError>> = Resource::new(
         || extract_params(),
         |params| async move {
             if let Ok((TimerPageParams { timer_id }, _)) = params {
@@ -999,8 +1668,14 @@ fn SettingsPage() -> impl IntoView {
         },
     );
     Effect::new(move || {
-        if let Some(Ok(duration_override)) = old_settings.get() {
-            duration_override_signal.set(Ok(duration_override));
+        if let Some(Ok(settings)) = old_settings.get() {
+            durations_text.set(settings.durations.to_string());
+            warnings_text.set(settings.warnings.to_string());
+            starting_stack_signal.set(Ok(settings.starting_stack));
+            table_size_signal.set(Ok(settings.table_size));
+            bounty_signal.set(settings.bounty);
+            entries_signal.set(settings.entries);
+            payouts_signal.set(settings.payouts);
         }
     });
     let device_id = get_device_id();
@@ -1027,7 +1702,10 @@ fn SettingsPage() -> impl IntoView {
                             });
                         }
                     };
-                    let error = duration_override_signal.get().is_err();
+                    let error = DurationOverrides::parse(&durations_text.get()).is_err()
+                        || Warnings::parse(&warnings_text.get()).is_err()
+                        || starting_stack_signal.get().is_err()
+                        || table_size_signal.get().is_err();
 
                     view! {
                         <CloseButton href=Some(format!("/{timer_id}/timer?{timer_url_query}")) />
@@ -1036,11 +1714,32 @@ fn SettingsPage() -> impl IntoView {
                             class="form"
                             on:submit:target=move |evt| {
                                 evt.prevent_default();
-                                if let Ok(v) = duration_override_signal.get() {
+                                if let (
+                                    Ok(durations),
+                                    Ok(warnings),
+                                    Ok(starting_stack),
+                                    Ok(table_size),
+                                ) = (
+                                    DurationOverrides::parse(&durations_text.get()),
+                                    Warnings::parse(&warnings_text.get()),
+                                    starting_stack_signal.get(),
+                                    table_size_signal.get(),
+                                ) {
+                                    let settings = TournamentConfig {
+                                        durations,
+                                        starting_stack,
+                                        entries: entries_signal.get(),
+                                        payouts: payouts_signal.get(),
+                                        table_size,
+                                        bounty: bounty_signal.get(),
+                                        warnings,
+                                    };
                                     let timer_url_query = timer_url_query.clone();
                                     spawn_local(async move {
-                                        if let Err(e) = set_tournament_settings(timer_id, v).await {
-                                            duration_override_signal.set(Err(e.to_string()));
+                                        if let Err(e) = set_tournament_settings(timer_id, settings)
+                                            .await
+                                        {
+                                            save_error.set(Some(e.to_string()));
                                         } else {
                                             let nav = use_navigate();
                                             nav(
@@ -1052,13 +1751,70 @@ fn SettingsPage() -> impl IntoView {
                                 }
                             }
                         >
-                            <InputOptionalDuration
-                                name="Duration Override".to_string()
-                                signal=duration_override_signal
+                            <InputDurations text=durations_text />
+                            <InputWarnings text=warnings_text />
+                            <InputOptionalNumber
+                                name="Starting Stack".to_string()
+                                signal=starting_stack_signal
+                            />
+                            {EntryKind::ALL
+                                .into_iter()
+                                .map(|kind| view! { <EntryRuleInput kind entries=entries_signal /> })
+                                .collect_view()}
+                            <PayoutInput payouts=payouts_signal />
+                            <BountyInput bounty=bounty_signal />
+                            <InputOptionalNumber
+                                name="Table Size".to_string()
+                                signal=table_size_signal
                             />
                             <button type="submit" disabled=error>
                                 "Save"
                             </button>
+                            {move || {
+                                save_error.get().map(|e| view! { <div class="error-message">{e}</div> })
+                            }}
+                        </form>
+                        <form
+                            class="form"
+                            on:submit={
+                                let execute_command = execute_command.clone();
+                                move |evt| {
+                                    evt.prevent_default();
+                                    if let Some(time) = parse_time(&time_left.get_untracked()) {
+                                        execute_command(Command::SetTime(time));
+                                    }
+                                }
+                            }
+                        >
+                            <div class="form-group">
+                                <label for="time_left">"Time Left in Level"</label>
+                                " "
+                                <input
+                                    type="text"
+                                    id="time_left"
+                                    placeholder="min or min:sec"
+                                    prop:value=move || time_left.get()
+                                    on:input:target=move |ev| time_left.set(ev.target().value())
+                                />
+                                <button
+                                    type="submit"
+                                    disabled=move || parse_time(&time_left.get()).is_none()
+                                >
+                                    "Set"
+                                </button>
+                            </div>
+                            <button type="button" on:click={
+                                let execute_command = execute_command.clone();
+                                move |_evt| {
+                                    execute_command(Command::AddTime(Duration::minutes(-1)));
+                                }
+                            }>"-1 Minute"</button>
+                            <button type="button" on:click={
+                                let execute_command = execute_command.clone();
+                                move |_evt| {
+                                    execute_command(Command::AddTime(Duration::minutes(1)));
+                                }
+                            }>"+1 Minute"</button>
                         </form>
                         <p>
                             <p>
@@ -1101,40 +1857,1021 @@ fn SettingsPage() -> impl IntoView {
     }
 }
 
-#[server]
+/// Register players, and bust them out as the tournament goes on
+#[component]
+fn PlayersPage() -> impl IntoView {
+    let device_id = get_device_id();
+    // bumped whenever we change the players so the list is reloaded
+    let version = RwSignal::new(0);
+    let roster = LocalResource::new(move || {
+        version.get();
+        async move {
+            match extract_params() {
+                Ok((TimerPageParams { timer_id }, _)) => tournament_roster(timer_id).await,
+                Err(e) => Err(ServerFnError::new(e)),
+            }
+        }
+    });
+    let name = RwSignal::new(String::new());
+    let seat = RwSignal::new(String::new());
+    let error = RwSignal::<Option<String>>::new(None);
+
+    view! {
+        {move || {
+            match extract_params() {
+                Ok((TimerPageParams { timer_id }, TimerNameQuery { name: timer_name, break_name })) => {
+                    let timer_url_query = timer_query(&timer_name, break_name.as_deref());
+                    let run = move |cmd: Command| {
+                        spawn_local(async move {
+                            match execute_command(cmd, timer_id, device_id).await {
+                                Ok(_) => error.set(None),
+                                Err(e) => error.set(Some(e.to_string())),
+                            }
+                            version.update(|v| *v += 1);
+                        });
+                    };
+                    view! {
+                        <CloseButton href=Some(format!("/{timer_id}/timer?{timer_url_query}")) />
+                        <Title text=format!("{timer_name} Players") />
+                        <h1>"Players"</h1>
+                        <a class="links" href=format!("/{timer_id}/deal?{timer_url_query}")>
+                            "Make a Deal"
+                        </a>
+                        " "
+                        <a class="links" rel="external" href=format!("/{timer_id}/results.csv")>
+                            "Download Results"
+                        </a>
+                        <form
+                            class="form"
+                            on:submit=move |ev| {
+                                ev.prevent_default();
+                                let seat = seat.get_untracked();
+                                let seat = if seat.trim().is_empty() {
+                                    None
+                                } else {
+                                    match parse_number(&seat) {
+                                        Some(seat) => Some(seat),
+                                        None => {
+                                            error.set(Some("The seat must be a number".to_string()));
+                                            return;
+                                        }
+                                    }
+                                };
+                                run(Command::RegisterPlayer {
+                                    name: name.get_untracked(),
+                                    seat,
+                                });
+                                name.set(String::new());
+                            }
+                        >
+                            <div class="form-group">
+                                <label for="player_name">"Name"</label>
+                                <input
+                                    type="text"
+                                    id="player_name"
+                                    prop:value=move || name.get()
+                                    on:input:target=move |ev| name.set(ev.target().value())
+                                />
+                            </div>
+                            <div class="form-group">
+                                <label for="player_seat">"Seat (optional)"</label>
+                                <input
+                                    type="text"
+                                    id="player_seat"
+                                    prop:value=move || seat.get()
+                                    on:input:target=move |ev| seat.set(ev.target().value())
+                                />
+                            </div>
+                            <button type="submit" disabled=move || name.get().trim().is_empty()>
+                                "Register"
+                            </button>
+                        </form>
+                        {move || error.get().map(|e| view! { <div class="error-message">{e}</div> })}
+                        {move || {
+                            let Roster { players, transactions, rules, eliminations, bounties } = match roster
+                                .get()
+                                .map(|r| r.take())
+                            {
+                                None => return view! { "Loading..." }.into_any(),
+                                Some(Err(e)) => return view! { <p>{e.to_string()}</p> }.into_any(),
+                                Some(Ok(roster)) => roster,
+                            };
+                            // buttons for the entries a player can pay for
+                            let entry_buttons = |id: Uuid, kinds: &[EntryKind]| {
+                                let paid = |kind| {
+                                    transactions.iter().any(|t| t.player == id && t.kind == kind)
+                                };
+                                kinds
+                                    .iter()
+                                    .copied()
+                                    .filter(|kind| rules.get(*kind).is_some())
+                                    .filter(|kind| match kind {
+                                        EntryKind::BuyIn | EntryKind::AddOn => !paid(*kind),
+                                        _ => true,
+                                    })
+                                    .map(|kind| {
+                                        view! {
+                                            <button on:click=move |_| {
+                                                run(Command::AddEntry { player: id, kind })
+                                            }>{kind.name()}</button>
+                                        }
+                                    })
+                                    .collect_view()
+                            };
+                            let spent = |id: Uuid| -> u32 {
+                                transactions.iter().filter(|t| t.player == id).map(|t| t.price).sum()
+                            };
+                            let bounty_text = |id: Uuid| {
+                                bounties
+                                    .iter()
+                                    .find(|b| b.player == id)
+                                    .map(|b| format!("Bounty {} Won {}", b.bounty, b.won))
+                            };
+                            let name_of = |id: Uuid| {
+                                players.iter().find(|p| p.id == id).map(|p| p.name.clone())
+                            };
+                            let knocked_out_by = |id: Uuid| {
+                                eliminations
+                                    .iter()
+                                    .rev()
+                                    .find(|e| e.player == id)
+                                    .and_then(|e| name_of(e.by))
+                                    .map(|name| format!("by {name}"))
+                            };
+                            let (mut busted, playing): (Vec<Player>, Vec<Player>) = players
+                                .iter()
+                                .cloned()
+                                .partition(|p| p.busted.is_some());
+                            // who can be picked as knocking a player out
+                            let opponents: Vec<(Uuid, String)> = playing
+                                .iter()
+                                .map(|p| (p.id, p.name.clone()))
+                                .collect();
+                            // the last player out finished the highest
+                            busted.sort_by_key(|p| std::cmp::Reverse(p.busted));
+                            let remaining = playing.len();
+                            view! {
+                                <h2>{format!("Playing ({remaining})")}</h2>
+                                <button on:click=move |_| run(Command::DrawSeats)>"Draw Seats"</button>
+                                <table class="players">
+                                    {playing
+                                        .into_iter()
+                                        .map(|p| {
+                                            let id = p.id;
+                                            let by = RwSignal::<Option<Uuid>>::new(None);
+                                            view! {
+                                                <tr>
+                                                    <td>{p.name}</td>
+                                                    <td>
+                                                        {match (p.table, p.seat) {
+                                                            (Some(table), Some(seat)) => {
+                                                                Some(Seat { table, seat }.to_string())
+                                                            }
+                                                            (None, Some(seat)) => Some(format!("Seat {seat}")),
+                                                            _ => None,
+                                                        }}
+                                                    </td>
+                                                    <td>{spent(id)}</td>
+                                                    <td>{bounty_text(id)}</td>
+                                                    <td>
+                                                        {entry_buttons(
+                                                            id,
+                                                            &[EntryKind::BuyIn, EntryKind::Rebuy, EntryKind::AddOn],
+                                                        )}
+                                                        <select on:change:target=move |ev| {
+                                                            by.set(ev.target().value().parse().ok())
+                                                        }>
+                                                            <option value="">"Knocked out by"</option>
+                                                            {opponents
+                                                                .iter()
+                                                                .filter(|(other, _)| *other != id)
+                                                                .map(|(other, name)| {
+                                                                    view! {
+                                                                        <option value=other.to_string()>{name.clone()}</option>
+                                                                    }
+                                                                })
+                                                                .collect_view()}
+                                                        </select>
+                                                        <button on:click=move |_| {
+                                                            run(Command::BustPlayer {
+                                                                player: id,
+                                                                by: by.get_untracked(),
+                                                            })
+                                                        }>"Bust Out"</button>
+                                                        <button on:click=move |_| {
+                                                            run(Command::UnregisterPlayer(id))
+                                                        }>"Remove"</button>
+                                                    </td>
+                                                </tr>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </table>
+                                <h2>"Busted Out"</h2>
+                                <table class="players">
+                                    {busted
+                                        .into_iter()
+                                        .enumerate()
+                                        .map(|(i, p)| {
+                                            view! {
+                                                <tr>
+                                                    <td>{remaining + i + 1}</td>
+                                                    <td>{p.name}</td>
+                                                    <td>{knocked_out_by(p.id)}</td>
+                                                    <td>{spent(p.id)}</td>
+                                                    <td>{bounty_text(p.id)}</td>
+                                                    <td>
+                                                        {entry_buttons(
+                                                            p.id,
+                                                            &[EntryKind::Rebuy, EntryKind::ReEntry],
+                                                        )}
+                                                    </td>
+                                                </tr>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </table>
+                            }
+                                .into_any()
+                        }}
+                    }
+                        .into_any()
+                }
+                Err(e) => view! { <p>Error: {e}</p> }.into_any(),
+            }
+        }}
+    }
+}
+
+/// Work out a chop for the players that are left
+#[component]
+fn DealPage() -> impl IntoView {
+    // the name and chip count text for each player in the deal
+    let rows = RwSignal::new(Vec::<(String, String)>::new());
+    let prizes = RwSignal::new(String::new());
+    let method = RwSignal::new(DealMethod::default());
+    let result = RwSignal::<Option<Result<Vec<(DealStack, u32)>, String>>>::new(None);
+    let row_count = Memo::new(move |_| rows.with(|r| r.len()));
+
+    let setup: Resource<Result<DealSetup, ServerFnError>> = Resource::new(
+        extract_params,
+        |params| async move {
+            if let Ok((TimerPageParams { timer_id }, _)) = params {
+                deal_defaults(timer_id).await
+            } else {
+                Err(ServerFnError::new("deal_defaults failed"))
+            }
+        },
+    );
+    Effect::new(move || {
+        if let Some(Ok(setup)) = setup.get() {
+            rows.set(setup.players.into_iter().map(|p| (p, String::new())).collect());
+            prizes.set(
+                setup
+                    .prizes
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+        }
+    });
+
+    let calculate = move || {
+        let stacks = rows.with_untracked(|rows| {
+            rows.iter()
+                .map(|(name, chips)| match parse_number(chips) {
+                    Some(chips) => Ok(DealStack {
+                        name: name.clone(),
+                        chips,
+                    }),
+                    None => Err(format!("How many chips does {name} have?")),
+                })
+                .collect::<Result<Vec<_>, String>>()
+        });
+        let amounts = prizes.with_untracked(|p| {
+            p.split(',')
+                .filter(|p| !p.trim().is_empty())
+                .map(parse_number)
+                .collect::<Option<Vec<_>>>()
+        });
+        let (stacks, amounts) = match (stacks, amounts) {
+            (Ok(stacks), Some(amounts)) => (stacks, amounts),
+            (Err(e), _) => return result.set(Some(Err(e))),
+            (_, None) => {
+                return result.set(Some(Err("The prizes must be numbers".to_string())));
+            }
+        };
+        let method = method.get_untracked();
+        spawn_local(async move {
+            result.set(Some(
+                calculate_deal(method, stacks.clone(), amounts)
+                    .await
+                    .map(|shares| stacks.into_iter().zip(shares).collect())
+                    .map_err(|e| e.to_string()),
+            ));
+        });
+    };
+
+    view! {
+        {move || {
+            match extract_params() {
+                Ok((TimerPageParams { timer_id }, TimerNameQuery { name: timer_name, break_name })) => {
+                    let timer_url_query = timer_query(&timer_name, break_name.as_deref());
+                    view! {
+                        <CloseButton href=Some(format!("/{timer_id}/players?{timer_url_query}")) />
+                        <Title text=format!("{timer_name} Deal") />
+                        <h1>"Make a Deal"</h1>
+                        <form
+                            class="form"
+                            on:submit=move |ev| {
+                                ev.prevent_default();
+                                calculate();
+                            }
+                        >
+                            <table class="players">
+                                {move || {
+                                    (0..row_count.get())
+                                        .map(|i| {
+                                            view! {
+                                                <tr>
+                                                    <td>
+                                                        <input
+                                                            type="text"
+                                                            placeholder="Name"
+                                                            prop:value=move || {
+                                                                rows.with(|r| r.get(i).map(|r| r.0.clone()))
+                                                            }
+                                                            on:input:target=move |ev| {
+                                                                rows.update(|r| r[i].0 = ev.target().value())
+                                                            }
+                                                        />
+                                                    </td>
+                                                    <td>
+                                                        <input
+                                                            type="text"
+                                                            placeholder="Chips"
+                                                            prop:value=move || {
+                                                                rows.with(|r| r.get(i).map(|r| r.1.clone()))
+                                                            }
+                                                            on:input:target=move |ev| {
+                                                                rows.update(|r| r[i].1 = ev.target().value())
+                                                            }
+                                                        />
+                                                    </td>
+                                                    <td>
+                                                        <button
+                                                            type="button"
+                                                            on:click=move |_| {
+                                                                rows.update(|r| {
+                                                                    r.remove(i);
+                                                                })
+                                                            }
+                                                        >
+                                                            "Remove"
+                                                        </button>
+                                                    </td>
+                                                </tr>
+                                            }
+                                        })
+                                        .collect_view()
+                                }}
+                            </table>
+                            <button
+                                type="button"
+                                on:click=move |_| rows.update(|r| r.push(Default::default()))
+                            >
+                                "Add Player"
+                            </button>
+                            <div class="form-group">
+                                <label for="deal_prizes">"Prizes left, starting with first"</label>
+                                <input
+                                    type="text"
+                                    id="deal_prizes"
+                                    placeholder="e.g. 500, 300, 200"
+                                    prop:value=move || prizes.get()
+                                    on:input:target=move |ev| prizes.set(ev.target().value())
+                                />
+                            </div>
+                            <div class="form-group">
+                                <label>"Method"</label>
+                                <select on:change:target=move |ev| {
+                                    let name = ev.target().value();
+                                    if let Some(m) = DealMethod::ALL.iter().find(|m| m.name() == name) {
+                                        method.set(*m);
+                                    }
+                                }>
+                                    {DealMethod::ALL
+                                        .iter()
+                                        .map(|m| {
+                                            let m = *m;
+                                            view! {
+                                                <option
+                                                    value=m.name()
+                                                    selected=move || method.get() == m
+                                                >
+                                                    {m.name()}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </select>
+                            </div>
+                            <button type="submit" disabled=move || row_count.get() < 2>
+                                "Work It Out"
+                            </button>
+                        </form>
+                        {move || match result.get() {
+                            None => ().into_any(),
+                            Some(Err(e)) => view! { <div class="error-message">{e}</div> }.into_any(),
+                            Some(Ok(shares)) => {
+                                view! {
+                                    <table class="players">
+                                        {shares
+                                            .into_iter()
+                                            .map(|(stack, amount)| {
+                                                view! {
+                                                    <tr>
+                                                        <td>{stack.name}</td>
+                                                        <td>{stack.chips}</td>
+                                                        <td>{amount}</td>
+                                                    </tr>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </table>
+                                }
+                                    .into_any()
+                            }
+                        }}
+                    }
+                        .into_any()
+                }
+                Err(e) => view! { <p>Error: {e}</p> }.into_any(),
+            }
+        }}
+    }
+}
+
+/// parse an optional number from a form field
+fn parse_number(s: &str) -> Option<u32> {
+    s.trim().parse().ok()
+}
+
+/// a list of numbers like "25, 100, 500", skipping anything that isn't a number
+fn parse_numbers(s: &str) -> Vec<u32> {
+    s.split(',').filter_map(parse_number).collect()
+}
+
+fn join_numbers(numbers: &[u32]) -> String {
+    numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// One editable row in the structure editor
+#[component]
+fn LevelRow(index: usize, level: LevelDef, levels: RwSignal<Vec<LevelDef>>) -> impl IntoView {
+    use icondata::{AiArrowDownOutlined, AiArrowUpOutlined, AiDeleteFilled};
+    let last = levels.with_untracked(|l| l.len() - 1);
+    let kind = level.kind.clone();
+    let number_field = move |label: &'static str,
+                             value: Option<u32>,
+                             set: fn(&mut LevelDef, Option<u32>)| {
+        view! {
+            <label>
+                {label}
+                <input
+                    type="number"
+                    min="0"
+                    class="level-number"
+                    prop:value=value.map(|v| v.to_string()).unwrap_or_default()
+                    on:change:target=move |ev| {
+                        let v = parse_number(&ev.target().value());
+                        levels.update(|l| set(&mut l[index], v));
+                    }
+                />
+            </label>
+        }
+    };
+
+    view! {
+        <tr>
+            <td>{index + 1}</td>
+            <td>
+                <select on:change:target=move |ev| {
+                    let kind = ev.target().value();
+                    levels.update(|l| l[index].set_kind(&kind));
+                }>
+                    {["blinds", "limit", "stud", "rotation", "break"]
+                        .into_iter()
+                        .map(|k| view! { <option value=k selected=kind == k>{k}</option> })
+                        .collect_view()}
+                </select>
+            </td>
+            <td>
+                {(kind != "break")
+                    .then(|| {
+                        view! {
+                            <label>
+                                "Game"
+                                <input
+                                    type="text"
+                                    class="level-game"
+                                    prop:value=level.game.clone().unwrap_or_default()
+                                    on:change:target=move |ev| {
+                                        let game = ev.target().value();
+                                        levels.update(|l| l[index].game = Some(game));
+                                    }
+                                />
+                            </label>
+                            {number_field("Small", level.small, |l, v| l.small = v)}
+                            {number_field("Big", level.big, |l, v| l.big = v)}
+                        }
+                    })}
+                {(kind == "rotation")
+                    .then(|| {
+                        view! {
+                            <label>
+                                "Games"
+                                <input
+                                    type="text"
+                                    class="level-games"
+                                    placeholder="e.g. Hold Em, Omaha 8, Razz, Stud, Stud 8"
+                                    prop:value=level.games.join(", ")
+                                    on:change:target=move |ev| {
+                                        let games = ev
+                                            .target()
+                                            .value()
+                                            .split(',')
+                                            .map(|g| g.trim().to_string())
+                                            .filter(|g| !g.is_empty())
+                                            .collect();
+                                        levels.update(|l| l[index].games = games);
+                                    }
+                                />
+                            </label>
+                            {number_field("Hands (blank for an orbit)", level.hands, |l, v| l.hands = v)}
+                        }
+                    })}
+                {(kind == "blinds")
+                    .then(|| {
+                        view! {
+                            <label>
+                                "Limit"
+                                <select on:change:target=move |ev| {
+                                    let limit = BettingLimit::ALL
+                                        .into_iter()
+                                        .find(|l| l.name() == ev.target().value());
+                                    levels.update(|l| l[index].betting_limit = limit);
+                                }>
+                                    <option value="" selected=level.betting_limit.is_none()>
+                                        "From the game"
+                                    </option>
+                                    {BettingLimit::ALL
+                                        .into_iter()
+                                        .map(|l| {
+                                            view! {
+                                                <option value=l.name() selected=level.betting_limit == Some(l)>
+                                                    {l.name()}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </select>
+                            </label>
+                        }
+                    })}
+                {(kind == "blinds" || kind == "stud" || kind == "rotation")
+                    .then(|| number_field("Ante", level.ante, |l, v| l.ante = v))}
+                {(kind == "blinds")
+                    .then(|| {
+                        let ante_kind = level.ante_kind.unwrap_or_default();
+                        view! {
+                            <label>
+                                "Paid By"
+                                <select on:change:target=move |ev| {
+                                    let ante_kind = AnteKind::ALL
+                                        .into_iter()
+                                        .find(|k| k.name() == ev.target().value())
+                                        .filter(|k| *k != AnteKind::Everyone);
+                                    levels.update(|l| l[index].ante_kind = ante_kind);
+                                }>
+                                    {AnteKind::ALL
+                                        .into_iter()
+                                        .map(|k| {
+                                            view! {
+                                                <option value=k.name() selected=ante_kind == k>
+                                                    {k.name()}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </select>
+                            </label>
+                        }
+                    })}
+                {(kind == "stud" || kind == "rotation")
+                    .then(|| number_field("Bring In", level.bring_in, |l, v| l.bring_in = v))}
+                {(kind == "break")
+                    .then(|| {
+                        view! {
+                            <label>
+                                "Color Up"
+                                <input
+                                    type="text"
+                                    class="level-chips"
+                                    placeholder="e.g. 25, 100"
+                                    prop:value=join_numbers(&level.color_up)
+                                    on:change:target=move |ev| {
+                                        let chips = parse_numbers(&ev.target().value());
+                                        levels.update(|l| l[index].color_up = chips);
+                                    }
+                                />
+                            </label>
+                        }
+                    })}
+                <label>
+                    "Minutes"
+                    <input
+                        type="number"
+                        min="1"
+                        class="level-number"
+                        prop:value=level.minutes.to_string()
+                        on:change:target=move |ev| {
+                            let minutes = ev.target().value().trim().parse().unwrap_or_default();
+                            levels.update(|l| l[index].minutes = minutes);
+                        }
+                    />
+                </label>
+            </td>
+            <td>
+                <button
+                    type="button"
+                    disabled=index == 0
+                    on:click=move |_| levels.update(|l| l.swap(index - 1, index))
+                >
+                    <Icon icon=AiArrowUpOutlined />
+                </button>
+                <button
+                    type="button"
+                    disabled=index == last
+                    on:click=move |_| levels.update(|l| l.swap(index, index + 1))
+                >
+                    <Icon icon=AiArrowDownOutlined />
+                </button>
+                <button
+                    type="button"
+                    on:click=move |_| {
+                        levels.update(|l| {
+                            l.remove(index);
+                        })
+                    }
+                >
+                    <Icon icon=AiDeleteFilled />
+                </button>
+            </td>
+        </tr>
+    }
+}
+
+#[derive(Params, PartialEq, Clone, Debug)]
+struct RawStructuresQuery {
+    timer_id: Option<Uuid>,
+    name: Option<String>,
+    break_name: Option<String>,
+}
+
+/// Build a new structure, or clone and edit an existing one. Saved structures
+/// show up in the structure dropdown when starting a tournament.
+///
+/// When we get here from a timer, the structure can also be used to start a
+/// tournament on that timer without saving it.
+#[component]
+fn StructuresPage() -> impl IntoView {
+    let for_timer = match use_query::<RawStructuresQuery>().get_untracked() {
+        Ok(RawStructuresQuery {
+            timer_id: Some(timer_id),
+            name: Some(name),
+            break_name,
+        }) => Some((timer_id, timer_query(&name, break_name.as_deref()))),
+        _ => None,
+    };
+    let structures = LocalResource::new(structure_names);
+    let name = RwSignal::new(String::new());
+    let levels = RwSignal::new(Vec::<LevelDef>::new());
+    let chips = RwSignal::new(Vec::<u32>::new());
+    // the result of the last load or save
+    let status = RwSignal::<Option<Result<String, String>>>::new(None);
+    let warnings = LocalResource::new(move || lint_structure(levels.get(), chips.get()));
+
+    let load = move |structure: String| {
+        if structure.is_empty() {
+            return;
+        }
+        spawn_local(async move {
+            match structure_levels(structure.clone()).await {
+                Ok((c, l)) => {
+                    name.set(structure);
+                    chips.set(c);
+                    levels.set(l);
+                    status.set(None);
+                }
+                Err(e) => status.set(Some(Err(e.to_string()))),
+            }
+        });
+    };
+    let add_level = move |kind: &str| {
+        levels.update(|l| {
+            // start with a copy of the last level of the same kind, that's
+            // usually closer to what you want than a blank row
+            let template = l
+                .iter()
+                .rev()
+                .find(|level| level.kind == kind)
+                .cloned()
+                .unwrap_or_else(|| LevelDef::new(kind));
+            l.push(template);
+        })
+    };
+
+    let close_href = for_timer
+        .as_ref()
+        .map(|(timer_id, timer_url_query)| format!("/{timer_id}/timer?{timer_url_query}"));
+
+    view! {
+        <CloseButton href=close_href />
+        <Title text="Structure Editor" />
+        <h1>"Structures"</h1>
+        <div class="form">
+            <div class="form-group">
+                <label for="start_from">"Start from"</label>
+                <select
+                    id="start_from"
+                    on:change:target=move |ev| {
+                        load(ev.target().value());
+                    }
+                >
+                    <option value="">"New structure"</option>
+                    {move || {
+                        structures
+                            .get()
+                            .and_then(|x| x.take().ok())
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|n| view! { <option value=n.clone()>{n.clone()}</option> })
+                            .collect_view()
+                    }}
+                </select>
+            </div>
+            <div class="form-group">
+                <label for="structure_name">"Name"</label>
+                <input
+                    type="text"
+                    id="structure_name"
+                    class="input-field"
+                    prop:value=move || name.get()
+                    on:input:target=move |ev| name.set(ev.target().value())
+                />
+            </div>
+            <div class="form-group">
+                <label for="structure_chips">"Chips (optional)"</label>
+                <input
+                    type="text"
+                    id="structure_chips"
+                    class="input-field"
+                    placeholder="e.g. 25, 100, 500, 1000"
+                    prop:value=move || join_numbers(&chips.get())
+                    on:change:target=move |ev| chips.set(parse_numbers(&ev.target().value()))
+                />
+            </div>
+        </div>
+        <table class="structure-editor">
+            {move || {
+                levels
+                    .get()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, level)| view! { <LevelRow index level levels /> })
+                    .collect_view()
+            }}
+        </table>
+        {move || {
+            if levels.with(|l| l.is_empty()) {
+                return None;
+            }
+            let warnings = match warnings.get()?.take() {
+                Ok(warnings) => warnings.iter().map(|w| w.to_string()).collect(),
+                Err(e) => vec![e.to_string()],
+            };
+            Some(
+                view! {
+                    <ul class="structure-warnings">
+                        {warnings
+                            .into_iter()
+                            .map(|w| view! { <li class="error-message">{w}</li> })
+                            .collect_view()}
+                    </ul>
+                },
+            )
+        }}
+        <p>
+            <button type="button" on:click=move |_| add_level("blinds")>"Add Blinds"</button>
+            <button type="button" on:click=move |_| add_level("limit")>"Add Limit"</button>
+            <button type="button" on:click=move |_| add_level("stud")>"Add Stud"</button>
+            <button type="button" on:click=move |_| add_level("rotation")>"Add Rotation"</button>
+            <button type="button" on:click=move |_| add_level("break")>"Add Break"</button>
+        </p>
+        <p>
+            <button
+                disabled=move || name.get().trim().is_empty() || levels.get().is_empty()
+                on:click=move |_| {
+                    spawn_local(async move {
+                        let result = save_structure(
+                                name.get_untracked(),
+                                levels.get_untracked(),
+                                chips.get_untracked(),
+                            )
+                            .await;
+                        status
+                            .set(
+                                Some(
+                                    result
+                                        .map(|_| "Saved".to_string())
+                                        .map_err(|e| e.to_string()),
+                                ),
+                            );
+                    });
+                }
+            >
+                "Save"
+            </button>
+            {for_timer
+                .map(|(timer_id, timer_url_query)| {
+                    view! {
+                        " "
+                        <button
+                            disabled=move || levels.get().is_empty()
+                            on:click=move |_| {
+                                let structure = match Structure::from_level_defs(
+                                    &levels.get_untracked(),
+                                    &chips.get_untracked(),
+                                ) {
+                                    Ok(structure) => structure,
+                                    Err(e) => {
+                                        status.set(Some(Err(e)));
+                                        return;
+                                    }
+                                };
+                                let mut structure_name = name.get_untracked().trim().to_string();
+                                if structure_name.is_empty() {
+                                    structure_name = "Custom".to_string();
+                                }
+                                let timer_url_query = timer_url_query.clone();
+                                spawn_local(async move {
+                                    match create_tournament(
+                                            timer_id,
+                                            structure_name,
+                                            Some(structure),
+                                            None,
+                                        )
+                                        .await
+                                    {
+                                        Ok(_) => {
+                                            use_navigate()(
+                                                &format!("/{timer_id}/timer?{timer_url_query}"),
+                                                NavigateOptions::default(),
+                                            )
+                                        }
+                                        Err(e) => status.set(Some(Err(e.to_string()))),
+                                    }
+                                });
+                            }
+                        >
+                            "Start Tournament"
+                        </button>
+                    }
+                })}
+        </p>
+        {move || {
+            status
+                .get()
+                .map(|status| match status {
+                    Ok(msg) => view! { <div>{msg}</div> }.into_any(),
+                    Err(e) => view! { <div class="error-message">{e}</div> }.into_any(),
+                })
+        }}
+    }
+}
+
+#[server(input = Json)]
 async fn set_tournament_settings(
     timer_id: Uuid,
-    duration_override: Option<Duration>,
+    settings: TournamentConfig,
 ) -> Result<(), ServerFnError> {
     use crate::timers::Timer;
-    Timer::get_mut(timer_id).update_settings(duration_override);
+    settings.payouts.check().map_err(ServerFnError::new)?;
+    if let Some(bounty) = &settings.bounty {
+        bounty.check(&settings.entries).map_err(ServerFnError::new)?;
+    }
+    Timer::open_mut(timer_id)?.update_settings(settings);
     Ok(())
 }
 
 #[server]
-async fn tournament_settings(timer_id: Uuid) -> Result<Option<Duration>, ServerFnError> {
+async fn tournament_settings(timer_id: Uuid) -> Result<TournamentConfig, ServerFnError> {
     use crate::timers::Timer;
-    match &Timer::get(timer_id).tournament {
-        Some(t) => Ok(t.duration_override),
+    let timer = Timer::find(timer_id);
+    match timer.as_ref().and_then(|t| t.tournament.as_ref()) {
+        Some(t) => Ok(t.settings()),
         None => Err(ServerFnError::new("running tournament")),
     }
 }
 
-#[server]
+#[server(input = Json)]
 async fn execute_command(
     cmd: Command,
     timer_id: Uuid,
     device_id: Option<Uuid>,
 ) -> Result<(), ServerFnError> {
     use crate::timers::Timer;
-    Timer::get_mut(timer_id).execute(&cmd, device_id);
-    Ok(())
+    Timer::open_mut(timer_id)?
+        .execute(&cmd, device_id)
+        .map_err(ServerFnError::new)
+}
+
+#[server]
+async fn tournament_roster(timer_id: Uuid) -> Result<Roster, ServerFnError> {
+    use crate::timers::Timer;
+    let timer = Timer::find(timer_id);
+    match timer.as_ref().and_then(|t| t.tournament.as_ref()) {
+        Some(t) => Ok(Roster {
+            players: t.players.all().to_vec(),
+            transactions: t.players.transactions().to_vec(),
+            rules: t.entries.clone(),
+            eliminations: t.players.eliminations().to_vec(),
+            bounties: t
+                .bounty
+                .as_ref()
+                .map(|b| crate::bounties::bounties(&t.players, b))
+                .unwrap_or_default(),
+        }),
+        None => Err(ServerFnError::new("No tournament running")),
+    }
+}
+
+#[server]
+async fn deal_defaults(timer_id: Uuid) -> Result<DealSetup, ServerFnError> {
+    use crate::timers::Timer;
+    let timer = Timer::find(timer_id);
+    match timer.as_ref().and_then(|t| t.tournament.as_ref()) {
+        Some(t) => {
+            Ok(crate::deals::deal_setup(&t.players, t.prize_pool().as_ref()))
+        }
+        None => Err(ServerFnError::new("No tournament running")),
+    }
+}
+
+/// What each player gets from a deal, in the same order as `stacks`
+#[server(input = Json)]
+async fn calculate_deal(
+    method: DealMethod,
+    stacks: Vec<DealStack>,
+    prizes: Vec<u32>,
+) -> Result<Vec<u32>, ServerFnError> {
+    crate::deals::deal(method, &stacks, &prizes).map_err(ServerFnError::new)
 }
 
 #[server]
 async fn structure_names() -> Result<Vec<String>, ServerFnError> {
-    Ok(crate::structures::STRUCTURES
-        .keys()
-        .map(|x| x.clone())
-        .collect())
+    Ok(crate::structures::structure_names())
+}
+
+/// The chips and levels of a structure, for editing
+#[server]
+async fn structure_levels(name: String) -> Result<(Vec<u32>, Vec<LevelDef>), ServerFnError> {
+    crate::structures::structure_levels(&name).ok_or(ServerFnError::new("Structure not found"))
+}
+
+#[server(input = Json)]
+async fn generate_structure(settings: GeneratorSettings) -> Result<Structure, ServerFnError> {
+    crate::generator::generate_structure(&settings).map_err(ServerFnError::new)
+}
+
+/// Check the levels from the structure editor for anything that looks wrong
+#[server(input = Json)]
+async fn lint_structure(
+    levels: Vec<LevelDef>,
+    chips: Vec<u32>,
+) -> Result<Vec<StructureWarning>, ServerFnError> {
+    let structure = Structure::from_level_defs(&levels, &chips).map_err(ServerFnError::new)?;
+    Ok(structure.lint())
+}
+
+#[server(input = Json)]
+async fn save_structure(
+    name: String,
+    levels: Vec<LevelDef>,
+    chips: Vec<u32>,
+) -> Result<(), ServerFnError> {
+    info!("Saving structure {name}");
+    crate::structures::save_structure(&name, levels, chips).map_err(ServerFnError::new)
 }
@@ -5,7 +5,10 @@ use crate::app::shell;
 use crate::model::TimerNameQuery;
 use crate::persistence::load_saved;
 use crate::persistence::save_running;
-use crate::timers::handle_socket;
+use crate::persistence::storage;
+use crate::persistence::{snapshot_interval, snapshot_periodically};
+use crate::structures::{STRUCTURES_DIR, lint_structures, load_structures};
+use crate::timers::{Timer, evict_idle_periodically, handle_socket, idle_timeout};
 use axum::Json;
 use axum::extract::Path;
 use axum::extract::Query;
@@ -15,7 +18,7 @@ use axum::http::StatusCode;
 use axum::http::header;
 use axum::response::IntoResponse;
 use image::Luma;
-use log::{error, info};
+use log::{error, info, warn};
 use once_cell::sync::Lazy;
 use qrcode::QrCode;
 use serde::Deserialize;
@@ -64,6 +67,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/:timer_id/ws/:device_id", any(websocket_handler))
         .route("/:timer_id/ws", any(websocket_handler_no_device))
         .route("/:timer_id/manifest.json", get(manifest))
+        .route("/:timer_id/results.csv", get(results_csv))
         .fallback(leptos_axum::file_and_error_handler(shell))
         .with_state(leptos_options);
 
@@ -71,7 +75,16 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let handle = axum_server::Handle::new();
     let handle2 = handle.clone();
     tokio::spawn(async { shutdown_signal(handle2).await });
+    for e in load_structures(std::path::Path::new(STRUCTURES_DIR)) {
+        error!("Couldn't load structure {e}");
+    }
+    for (name, warning) in lint_structures() {
+        warn!("Structure {name} {warning}");
+    }
     load_saved()?;
+    // only after loading, so an empty snapshot can't replace the one we need
+    tokio::spawn(snapshot_periodically(snapshot_interval()));
+    tokio::spawn(evict_idle_periodically(idle_timeout()));
 
     if addr.port() == 8443 {
         // we want a https server
@@ -240,6 +253,34 @@ pub async fn qr_code(
     ([(header::CONTENT_TYPE, "image/png")], buf.into_inner()).into_response()
 }
 
+/// Download the results of the tournament
+pub async fn results_csv(Path(timer_id): Path<Uuid>) -> impl IntoResponse {
+    let timer = Timer::find(timer_id);
+    let results = match timer.as_ref().and_then(|t| t.tournament.as_ref()) {
+        Some(tournament) => tournament.results(),
+        None => {
+            drop(timer);
+            // the results are still there once it's over
+            match storage().last_ended(timer_id) {
+                Ok(Some(last)) => crate::results::results(&last.players, &last.payouts, last.bounty.as_ref()),
+                Ok(None) => return (StatusCode::NOT_FOUND, "No tournament results").into_response(),
+                Err(e) => {
+                    error!("Couldn't read how {timer_id} ended: {e}");
+                    return (StatusCode::INTERNAL_SERVER_ERROR, "Couldn't read the results").into_response();
+                }
+            }
+        }
+    };
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"results.csv\""),
+        ],
+        crate::results::results_csv(&results),
+    )
+        .into_response()
+}
+
 pub async fn manifest(
     Path(timer_id): Path<Uuid>,
     Query(TimerNameQuery {
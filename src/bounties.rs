@@ -0,0 +1,161 @@
+//! Keeps track of the bounties in a bounty or progressive knockout
+//! tournament.
+//!
+//! Nothing about the bounties is stored. They are worked out by replaying
+//! the entries and knockouts in the order they happened, so changing the
+//! bounty settings changes the totals for the whole tournament.
+
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::model::*;
+use crate::players::Players;
+
+/// the entries that pay for a bounty
+fn pays_bounty(kind: EntryKind) -> bool {
+    matches!(kind, EntryKind::BuyIn | EntryKind::Rebuy | EntryKind::ReEntry)
+}
+
+/// All the money that has gone to bounties
+pub fn bounty_pool(players: &Players, config: &BountyConfig) -> u32 {
+    players
+        .transactions()
+        .iter()
+        .filter(|t| pays_bounty(t.kind))
+        .map(|t| config.amount.min(t.price))
+        .sum()
+}
+
+/// Where the bounties stand for every player, in the order they registered
+pub fn bounties(players: &Players, config: &BountyConfig) -> Vec<PlayerBounty> {
+    enum Event<'a> {
+        Entry(&'a Transaction),
+        Knockout(&'a Elimination),
+    }
+    let mut events: Vec<(DateTime, Event)> = players
+        .transactions()
+        .iter()
+        .filter(|t| pays_bounty(t.kind))
+        .map(|t| (t.time, Event::Entry(t)))
+        .chain(
+            players
+                .eliminations()
+                .iter()
+                .map(|e| (e.time, Event::Knockout(e))),
+        )
+        .collect();
+    events.sort_by_key(|(time, _)| *time);
+
+    let cash_percent = match config.kind {
+        BountyKind::Fixed => 100,
+        BountyKind::Progressive { cash_percent } => cash_percent.min(100),
+    };
+    let mut totals: HashMap<Uuid, PlayerBounty> = players
+        .all()
+        .iter()
+        .map(|p| {
+            (
+                p.id,
+                PlayerBounty {
+                    player: p.id,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+    for (_, event) in events {
+        match event {
+            Event::Entry(t) => {
+                if let Some(b) = totals.get_mut(&t.player) {
+                    b.bounty += config.amount.min(t.price);
+                }
+            }
+            Event::Knockout(e) => {
+                let Some(loser) = totals.get_mut(&e.player) else {
+                    continue;
+                };
+                let bounty = std::mem::take(&mut loser.bounty);
+                let cash = bounty * cash_percent / 100;
+                if let Some(winner) = totals.get_mut(&e.by) {
+                    winner.won += cash;
+                    winner.bounty += bounty - cash;
+                    winner.knockouts += 1;
+                }
+            }
+        }
+    }
+    players
+        .all()
+        .iter()
+        .filter_map(|p| totals.remove(&p.id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> EntryRules {
+        let rule = Some(EntryRule {
+            price: 100,
+            chips: 1000,
+            last_level: None,
+        });
+        EntryRules {
+            buy_in: rule.clone(),
+            re_entry: rule,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn progressive_bounties_grow() {
+        let rules = rules();
+        let config = BountyConfig {
+            amount: 40,
+            kind: BountyKind::Progressive { cash_percent: 50 },
+        };
+        let mut players = Players::default();
+        let a = players.enter("A", None, &rules, 1).unwrap();
+        let b = players.enter("B", None, &rules, 1).unwrap();
+        let c = players.enter("C", None, &rules, 1).unwrap();
+        players.knock_out(b, Some(a)).unwrap();
+        players.knock_out(a, Some(c)).unwrap();
+        let bounties = bounties(&players, &config);
+        // A won 20 from B and added 20 to their own bounty, then C took half of 60
+        assert_eq!((bounties[0].bounty, bounties[0].won, bounties[0].knockouts), (0, 20, 1));
+        assert_eq!((bounties[1].bounty, bounties[1].won), (0, 0));
+        assert_eq!((bounties[2].bounty, bounties[2].won, bounties[2].knockouts), (70, 30, 1));
+        // the money adds up
+        let total: u32 = bounties.iter().map(|b| b.bounty + b.won).sum();
+        assert_eq!(total, bounty_pool(&players, &config));
+
+        // a re-entry starts with a fresh bounty
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        players.add_entry(b, EntryKind::ReEntry, &rules, 2).unwrap();
+        assert_eq!(super::bounties(&players, &config)[1].bounty, 40);
+    }
+
+    #[test]
+    fn fixed_bounties_are_paid_in_full() {
+        let rules = rules();
+        let config = BountyConfig {
+            amount: 25,
+            kind: BountyKind::Fixed,
+        };
+        let mut players = Players::default();
+        let a = players.enter("A", None, &rules, 1).unwrap();
+        let b = players.enter("B", None, &rules, 1).unwrap();
+        let c = players.enter("C", None, &rules, 1).unwrap();
+        assert!(players.knock_out(a, Some(a)).is_err());
+        players.knock_out(b, Some(a)).unwrap();
+        assert!(players.knock_out(c, Some(b)).is_err());
+        players.knock_out(c, None).unwrap();
+        let bounties = bounties(&players, &config);
+        assert_eq!((bounties[0].bounty, bounties[0].won), (25, 25));
+        // nobody collected C's bounty
+        assert_eq!(bounties[2].bounty, 25);
+        assert_eq!(players.eliminations().len(), 1);
+    }
+}
@@ -0,0 +1,189 @@
+//! Works out what each player should get when the players left agree to
+//! split the prize pool instead of playing it out.
+
+use crate::model::*;
+use crate::players::Players;
+
+/// ICM looks at every order the players could finish in, so it gets slow
+/// quickly. Nobody chops with more than a final table left anyway.
+const MAX_ICM_PLAYERS: usize = 12;
+
+/// The players still in and the prizes they're playing for
+pub fn deal_setup(players: &Players, pool: Option<&PrizePool>) -> DealSetup {
+    let remaining = players.remaining();
+    DealSetup {
+        players: players
+            .all()
+            .iter()
+            .filter(|p| p.busted.is_none())
+            .map(|p| p.name.clone())
+            .collect(),
+        prizes: pool
+            .map(|pool| {
+                pool.payouts
+                    .iter()
+                    .filter(|p| p.place <= remaining)
+                    .map(|p| p.amount)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// What each player gets from the deal, in the same order as `stacks`. The
+/// amounts always add up to the prizes for the places left.
+pub fn deal(method: DealMethod, stacks: &[DealStack], prizes: &[u32]) -> Result<Vec<u32>, String> {
+    if stacks.len() < 2 {
+        return Err("A deal needs at least 2 players".to_string());
+    }
+    if let Some(s) = stacks.iter().find(|s| s.chips == 0) {
+        return Err(format!("{} has no chips", s.name));
+    }
+    // only the places the players left can finish in are in play
+    let mut prizes = prizes.to_vec();
+    prizes.resize(stacks.len(), 0);
+    let chips: Vec<u32> = stacks.iter().map(|s| s.chips).collect();
+    let equity = match method {
+        DealMethod::Icm => {
+            if stacks.len() > MAX_ICM_PLAYERS {
+                return Err(format!(
+                    "ICM can only be worked out for {MAX_ICM_PLAYERS} players or fewer"
+                ));
+            }
+            icm(&chips, &prizes)
+        }
+        DealMethod::ChipChop => chip_chop(&chips, &prizes),
+    };
+    let total: u64 = prizes.iter().map(|p| *p as u64).sum();
+    Ok(round_shares(&equity, total))
+}
+
+/// The expected prize for each player under the Independent Chip Model: the
+/// chance of finishing first is your share of the chips, and the chance of
+/// finishing in each lower place is worked out the same way from the players
+/// left once the higher places are taken.
+pub fn icm(chips: &[u32], prizes: &[u32]) -> Vec<f64> {
+    // memo[mask] is the equity of each player when only the players in mask
+    // are still fighting over the remaining places
+    let mut memo: Vec<Option<Vec<f64>>> = vec![None; 1 << chips.len()];
+    icm_equity(chips, prizes, (1 << chips.len()) - 1, &mut memo)
+}
+
+fn icm_equity(
+    chips: &[u32],
+    prizes: &[u32],
+    mask: usize,
+    memo: &mut Vec<Option<Vec<f64>>>,
+) -> Vec<f64> {
+    if let Some(equity) = &memo[mask] {
+        return equity.clone();
+    }
+    let mut equity = vec![0.0; chips.len()];
+    // the places above the players in mask are already taken
+    let place = chips.len() - mask.count_ones() as usize;
+    if place < prizes.len() {
+        let in_mask = |i: usize| mask & (1 << i) != 0;
+        let total: f64 = (0..chips.len())
+            .filter(|i| in_mask(*i))
+            .map(|i| chips[i] as f64)
+            .sum();
+        for i in (0..chips.len()).filter(|i| in_mask(*i)) {
+            let chance = chips[i] as f64 / total;
+            equity[i] += chance * prizes[place] as f64;
+            let rest = icm_equity(chips, prizes, mask & !(1 << i), memo);
+            for (e, r) in equity.iter_mut().zip(rest) {
+                *e += chance * r;
+            }
+        }
+    }
+    memo[mask] = Some(equity.clone());
+    equity
+}
+
+/// Everyone is guaranteed the smallest prize left, and the rest of the money
+/// is split by the chip counts
+pub fn chip_chop(chips: &[u32], prizes: &[u32]) -> Vec<f64> {
+    let guaranteed = prizes.iter().copied().min().unwrap_or(0) as f64;
+    let rest = prizes.iter().map(|p| *p as f64).sum::<f64>() - guaranteed * chips.len() as f64;
+    let total: f64 = chips.iter().map(|c| *c as f64).sum();
+    chips
+        .iter()
+        .map(|c| guaranteed + rest * *c as f64 / total)
+        .collect()
+}
+
+/// Round the shares down to whole amounts, then hand out what is left over
+/// one at a time to the shares that lost the most in the rounding
+fn round_shares(equity: &[f64], total: u64) -> Vec<u32> {
+    let mut amounts: Vec<u32> = equity.iter().map(|e| e.floor() as u32).collect();
+    let mut leftover = total.saturating_sub(amounts.iter().map(|a| *a as u64).sum());
+    let mut order: Vec<usize> = (0..equity.len()).collect();
+    order.sort_by(|a, b| {
+        let fa = equity[*a] - equity[*a].floor();
+        let fb = equity[*b] - equity[*b].floor();
+        fb.total_cmp(&fa)
+    });
+    for i in order.into_iter().cycle() {
+        if leftover == 0 {
+            break;
+        }
+        amounts[i] += 1;
+        leftover -= 1;
+    }
+    amounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stacks(chips: &[u32]) -> Vec<DealStack> {
+        chips
+            .iter()
+            .enumerate()
+            .map(|(i, chips)| DealStack {
+                name: format!("Player {i}"),
+                chips: *chips,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn icm_favors_the_short_stacks() {
+        let equity = icm(&[5000, 3000, 2000], &[50, 30, 20]);
+        assert!((equity.iter().sum::<f64>() - 100.0).abs() < 1e-9);
+        // the chip leader gets less than their share of the chips
+        assert!(equity[0] < 50.0 && equity[0] > 38.0);
+        assert!(equity[2] > 20.0);
+        assert_eq!(
+            deal(DealMethod::Icm, &stacks(&[5000, 3000, 2000]), &[50, 30, 20]),
+            Ok(vec![38, 33, 29])
+        );
+        // equal stacks split evenly
+        assert_eq!(
+            deal(DealMethod::Icm, &stacks(&[100, 100]), &[700, 300]),
+            Ok(vec![500, 500])
+        );
+    }
+
+    #[test]
+    fn chip_chop_splits_above_the_smallest_prize() {
+        assert_eq!(
+            deal(DealMethod::ChipChop, &stacks(&[6000, 4000]), &[700, 300]),
+            Ok(vec![540, 460])
+        );
+        // more players than prizes, so nobody is guaranteed anything
+        assert_eq!(
+            deal(DealMethod::ChipChop, &stacks(&[1, 1, 1]), &[100]),
+            Ok(vec![34, 33, 33])
+        );
+    }
+
+    #[test]
+    fn bad_deals_are_rejected() {
+        assert!(deal(DealMethod::Icm, &stacks(&[100]), &[100]).is_err());
+        assert!(deal(DealMethod::Icm, &stacks(&[100, 0]), &[100]).is_err());
+        assert!(deal(DealMethod::Icm, &stacks(&[1; 13]), &[100]).is_err());
+        assert!(deal(DealMethod::ChipChop, &stacks(&[1; 13]), &[100]).is_ok());
+    }
+}
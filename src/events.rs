@@ -0,0 +1,448 @@
+//! An append-only log of everything that happens to each timer's tournament,
+//! one JSON object to a line in `./storage/events/<timer id>.jsonl` (or in the
+//! database, see `persistence::Storage`). When the server starts, the
+//! tournaments are rebuilt exactly by replaying the logs, and the logs are a
+//! record of what happened during the night.
+//!
+//! For a replay to end up where the tournament was, every event is run at the
+//! time it was logged, and gets its random numbers (seat draws, player ids)
+//! from a seed that is logged with it. See `during`.
+//!
+//! Once a tournament ends, its log is compacted down to the final state.
+
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use log::{error, info, warn};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    backend::Subscription,
+    model::*,
+    persistence::{StoredTournament, read_records},
+    timers::Timer,
+};
+
+/// Where the event logs are kept
+pub const EVENTS_DIR: &str = "./storage/events";
+
+/// Something that changed a tournament
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum TimerEvent {
+    /// A tournament started, or was loaded from a snapshot
+    Started(Box<StoredTournament>),
+    Command {
+        command: Command,
+        device_id: Option<Uuid>,
+    },
+    Settings(TournamentConfig),
+    Subscribed {
+        device_id: Uuid,
+        subscription: Subscription,
+    },
+    Unsubscribed(Uuid),
+    /// The clock ran out and the next level started
+    LevelUp,
+    /// The scheduled start time came
+    Begin,
+    /// Only written by compaction, with the tournament as it was when it
+    /// ended
+    Ended(Box<StoredTournament>),
+}
+
+impl TimerEvent {
+    /// Whether the tournament can end because of this event
+    pub fn can_end(&self) -> bool {
+        matches!(
+            self,
+            TimerEvent::LevelUp
+                | TimerEvent::Command {
+                    command: Command::Terminate | Command::NextLevel,
+                    ..
+                }
+        )
+    }
+}
+
+/// One line of a log
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct LogEntry {
+    pub at: DateTime,
+    /// where the random numbers for the event come from
+    pub seed: u64,
+    pub event: TimerEvent,
+}
+
+impl LogEntry {
+    /// An event happening now
+    pub fn new(event: TimerEvent) -> LogEntry {
+        LogEntry {
+            at: now(),
+            seed: rand::random(),
+            event,
+        }
+    }
+}
+
+/// A timer and the entries of its log, oldest first
+pub type Log = (Uuid, Vec<LogEntry>);
+
+/// The event that is being run
+struct Context {
+    at: DateTime,
+    rng: StdRng,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+    static REPLAYING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` as `entry` happening: `now()` is the time of the entry, and
+/// `with_rng` and `new_id` get their random numbers from its seed
+pub fn during<T>(entry: &LogEntry, f: impl FnOnce() -> T) -> T {
+    let previous = CONTEXT.replace(Some(Context {
+        at: entry.at,
+        rng: StdRng::seed_from_u64(entry.seed),
+    }));
+    let result = f();
+    CONTEXT.set(previous);
+    result
+}
+
+/// The time of the event being run, if there is one
+pub fn event_time() -> Option<DateTime> {
+    CONTEXT.with_borrow(|c| c.as_ref().map(|c| c.at))
+}
+
+/// Random numbers for the event being run, or from entropy outside of one
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    CONTEXT.with_borrow_mut(|c| match c {
+        Some(context) => f(&mut context.rng),
+        None => f(&mut StdRng::from_entropy()),
+    })
+}
+
+/// A new random id, that is the same when the event is replayed
+pub fn new_id() -> Uuid {
+    with_rng(|rng| uuid::Builder::from_random_bytes(rng.r#gen()).into_uuid())
+}
+
+/// Whether the logs are being replayed, so nobody needs to be told about the
+/// events and they mustn't be logged again
+pub fn replaying() -> bool {
+    REPLAYING.get()
+}
+
+fn log_path(dir: &Path, timer_id: Uuid) -> PathBuf {
+    dir.join(format!("{timer_id}.jsonl"))
+}
+
+/// Add an entry to the end of a timer's log in `dir`
+pub(crate) fn append_to(dir: &Path, timer_id: Uuid, entry: &LogEntry) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path(dir, timer_id))?;
+    file.write_all(&line)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// Replace a timer's log in `dir` with the tournament as it was when it ended
+pub(crate) fn compact_in(
+    dir: &Path,
+    timer_id: Uuid,
+    last: &StoredTournament,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = log_path(dir, timer_id);
+    let temp = path.with_extension("jsonl.tmp");
+    let mut line = serde_json::to_vec(&LogEntry::new(TimerEvent::Ended(Box::new(last.clone()))))?;
+    line.push(b'\n');
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(&line)?;
+    file.sync_all()?;
+    fs::rename(&temp, &path)?;
+    Ok(())
+}
+
+/// How the last tournament in a timer's log in `dir` ended, if it has. The
+/// lines that can't be read were quarantined when the server started.
+pub(crate) fn last_ended_in(dir: &Path, timer_id: Uuid) -> std::io::Result<Option<StoredTournament>> {
+    let path = log_path(dir, timer_id);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut last = None;
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        if let Ok(LogEntry {
+            event: TimerEvent::Ended(ended),
+            ..
+        }) = serde_json::from_str(&line?)
+        {
+            last = Some(*ended);
+        }
+    }
+    Ok(last)
+}
+
+/// Read a log. The lines that can't be read, like the one the server went
+/// down in the middle of writing, are added to `dead_letters` and taken out
+/// of the log, and the rest are kept.
+fn read_log(path: &Path, dead_letters: &Path) -> std::io::Result<Vec<LogEntry>> {
+    let records = BufReader::new(fs::File::open(path)?)
+        .lines()
+        .map(|line| line.map(|line| serde_json::from_str(&line).unwrap_or(Value::String(line))))
+        .collect::<std::io::Result<Vec<Value>>>()?;
+    let count = records.len();
+    let entries: Vec<LogEntry> = read_records(records, &path.display().to_string(), dead_letters);
+    if entries.len() < count {
+        // so they aren't quarantined again, and the next entry starts on a
+        // line of its own
+        warn!("Rewriting {} without the lines that can't be read", path.display());
+        let temp = path.with_extension("jsonl.tmp");
+        let mut file = fs::File::create(&temp)?;
+        for entry in &entries {
+            let mut line = serde_json::to_vec(entry)?;
+            line.push(b'\n');
+            file.write_all(&line)?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, path)?;
+    }
+    Ok(entries)
+}
+
+/// The timers with logs in `dir`
+fn logs(dir: &Path) -> std::io::Result<Vec<(Uuid, PathBuf)>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut logs: Vec<(Uuid, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let timer_id = name.strip_suffix(".jsonl")?.parse().ok()?;
+            Some((timer_id, path))
+        })
+        .collect();
+    logs.sort();
+    Ok(logs)
+}
+
+/// The logs of every timer in `dir`. A log that can't be read is left out.
+pub(crate) fn read_logs(dir: &Path, dead_letters: &Path) -> std::io::Result<Vec<Log>> {
+    let mut read = vec![];
+    for (timer_id, path) in logs(dir)? {
+        match read_log(&path, dead_letters) {
+            Ok(entries) => read.push((timer_id, entries)),
+            Err(e) => error!("Couldn't read {}: {e}", path.display()),
+        }
+    }
+    Ok(read)
+}
+
+/// Run the entries of a log against a timer
+pub(crate) fn replay(timer: &mut Timer, entries: &[LogEntry]) {
+    REPLAYING.set(true);
+    for entry in entries {
+        if let Err(e) = timer.apply(entry) {
+            // it failed the same way when it was logged
+            warn!("Replaying {} for {}: {e}", entry.at, timer.timer_id);
+        }
+    }
+    REPLAYING.set(false);
+}
+
+/// Rebuild the tournaments from the logs. Returns all the timers whose log
+/// has their tournament, even the ones that are over. A log without its
+/// start can't rebuild anything, so those timers are left to the snapshot.
+pub fn replay_logs(logs: Vec<Log>) -> Vec<Uuid> {
+    let mut replayed = vec![];
+    for (timer_id, entries) in logs {
+        let has_tournament = entries
+            .iter()
+            .any(|e| matches!(e.event, TimerEvent::Started(_) | TimerEvent::Ended(_)));
+        if !has_tournament {
+            warn!("The log for {timer_id} doesn't have its tournament, leaving it to the snapshot");
+            continue;
+        }
+        let mut timer = Timer::get_mut(timer_id);
+        replay(&mut timer, &entries);
+        if timer.tournament.is_some() {
+            info!("Replayed {} events for {timer_id}", entries.len());
+        }
+        replayed.push(timer_id);
+    }
+    replayed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored() -> StoredTournament {
+        serde_json::from_str(
+            r#"{
+                "timer_id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+                "created": "2025-03-01T19:00:00-05:00",
+                "structure_name": "Nightly NLHE",
+                "level": 1,
+                "clock_paused": true,
+                "clock_remaining": [600, 0],
+                "clock_asof": "2025-03-01T19:00:00-05:00",
+                "subscriptions": {}
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn command(command: Command) -> LogEntry {
+        LogEntry::new(TimerEvent::Command {
+            command,
+            device_id: None,
+        })
+    }
+
+    #[test]
+    fn events_happen_the_same_way_twice() {
+        let entry = command(Command::DrawSeats);
+        let first = during(&entry, || (now(), new_id(), with_rng(|r| r.r#gen::<u64>())));
+        let second = during(&entry, || (now(), new_id(), with_rng(|r| r.r#gen::<u64>())));
+        assert_eq!(first, second);
+        assert_eq!(first.0, entry.at);
+        assert_eq!(event_time(), None);
+        assert_ne!(new_id(), new_id());
+    }
+
+    #[test]
+    fn replaying_gets_the_same_tournament() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let settings = TournamentConfig {
+                table_size: Some(2),
+                ..TournamentConfig::default()
+            };
+            let mut entries = vec![
+                LogEntry::new(TimerEvent::Started(Box::new(stored()))),
+                LogEntry::new(TimerEvent::Settings(settings)),
+            ];
+            for name in ["Alice", "Bob", "Carol", "Dave"] {
+                entries.push(command(Command::RegisterPlayer {
+                    name: name.to_string(),
+                    seat: None,
+                }));
+            }
+            entries.push(command(Command::DrawSeats));
+            entries.push(LogEntry::new(TimerEvent::LevelUp));
+
+            let replayed: Vec<String> = (0..2)
+                .map(|_| {
+                    let mut timer = Timer::get_mut(Uuid::new_v4());
+                    replay(&mut timer, &entries);
+                    let t = timer.tournament.as_ref().unwrap();
+                    assert_eq!(t.level.number(), 2);
+                    serde_json::to_string(&t.players).unwrap()
+                })
+                .collect();
+            assert_eq!(replayed[0], replayed[1]);
+        });
+    }
+
+    #[test]
+    fn entries_that_cant_be_read_are_quarantined() {
+        let dir = std::env::temp_dir().join(format!("pokertimer-{}", Uuid::new_v4()));
+        let dead_letters = dir.join("dead-letters.jsonl");
+        let timer_id = Uuid::new_v4();
+        append_to(&dir, timer_id, &LogEntry::new(TimerEvent::Started(Box::new(stored())))).unwrap();
+        let path = log_path(&dir, timer_id);
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"at\": \"yesterday\", \"event\": \"Unknown\"}\n").unwrap();
+        append_to(&dir, timer_id, &command(Command::Resume)).unwrap();
+
+        // the entries on either side of it are still there
+        let entries = read_log(&path, &dead_letters).unwrap();
+        assert_eq!(entries.len(), 2);
+        let quarantined: Value =
+            serde_json::from_str(fs::read_to_string(&dead_letters).unwrap().trim()).unwrap();
+        assert_eq!(quarantined["record"]["event"], "Unknown");
+        assert_eq!(quarantined["source"], path.display().to_string());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn logs_without_their_tournament_are_left_to_the_snapshot() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let started = Uuid::new_v4();
+            let ended = Uuid::new_v4();
+            let headless = Uuid::new_v4();
+            let replayed = replay_logs(vec![
+                (started, vec![LogEntry::new(TimerEvent::Started(Box::new(stored())))]),
+                (ended, vec![LogEntry::new(TimerEvent::Ended(Box::new(stored())))]),
+                (headless, vec![command(Command::Resume)]),
+                (Uuid::new_v4(), vec![]),
+            ]);
+            assert_eq!(replayed, vec![started, ended]);
+            assert!(Timer::get(started).tournament.is_some());
+        });
+    }
+
+    #[test]
+    fn logs_are_appended_and_compacted() {
+        let dir = std::env::temp_dir().join(format!("pokertimer-{}", Uuid::new_v4()));
+        let timer_id = Uuid::new_v4();
+        append_to(&dir, timer_id, &LogEntry::new(TimerEvent::Started(Box::new(stored())))).unwrap();
+        append_to(&dir, timer_id, &command(Command::Resume)).unwrap();
+        assert!(last_ended_in(&dir, timer_id).unwrap().is_none());
+        let path = log_path(&dir, timer_id);
+        // the server went down half way through a line
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"at\":").unwrap();
+        assert_eq!(logs(&dir).unwrap(), vec![(timer_id, path.clone())]);
+        let dead_letters = dir.join("dead-letters.jsonl");
+        let entries = read_log(&path, &dead_letters).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(
+            entries[1].event,
+            TimerEvent::Command {
+                command: Command::Resume,
+                ..
+            }
+        ));
+        // the half a line is quarantined once, and the next entry gets a
+        // line of its own
+        assert_eq!(fs::read_to_string(&dead_letters).unwrap().lines().count(), 1);
+        append_to(&dir, timer_id, &command(Command::Pause)).unwrap();
+        assert_eq!(read_log(&path, &dead_letters).unwrap().len(), 3);
+        assert_eq!(fs::read_to_string(&dead_letters).unwrap().lines().count(), 1);
+
+        compact_in(&dir, timer_id, &stored()).unwrap();
+        let entries = read_log(&path, &dead_letters).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(entries[0].event, TimerEvent::Ended(_)));
+        // the results are kept until the next tournament ends
+        let ended = last_ended_in(&dir, timer_id).unwrap().unwrap();
+        assert_eq!(ended.structure_name, "Nightly NLHE");
+        append_to(&dir, timer_id, &LogEntry::new(TimerEvent::Started(Box::new(stored())))).unwrap();
+        assert!(last_ended_in(&dir, timer_id).unwrap().is_some());
+        assert!(last_ended_in(&dir, Uuid::new_v4()).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,298 @@
+//! Builds a blind structure from a handful of settings so that nobody has to
+//! type in 20 levels by hand.
+//!
+//! The big blind starts at about 1% of the starting stack and grows
+//! geometrically until, at the end of the target length, it is about 5% of
+//! the chips in play. Every blind is rounded to a value that looks like a real
+//! blind and can be made with the smallest chip.
+
+use crate::model::*;
+
+const DAY_MINUTES: i64 = 24 * 60;
+
+/// The most levels there can be: a day of one minute levels
+const MAX_LEVELS: usize = DAY_MINUTES as usize;
+
+/// Make a structure from the generator settings
+pub fn generate_structure(settings: &GeneratorSettings) -> Result<Structure, String> {
+    let GeneratorSettings {
+        game,
+        starting_stack,
+        players,
+        hours,
+        level_minutes,
+        smallest_chip,
+        break_every,
+        break_minutes,
+        antes_from,
+    } = settings;
+    if game.trim().is_empty() {
+        return Err("The structure needs a game".to_string());
+    }
+    if *players < 2 {
+        return Err("There must be at least 2 players".to_string());
+    }
+    if *level_minutes <= 0 {
+        return Err("Levels must be at least a minute long".to_string());
+    }
+    if *level_minutes > DAY_MINUTES {
+        return Err("Levels can't be longer than a day".to_string());
+    }
+    if *smallest_chip == 0 {
+        return Err("The smallest chip must be more than 0".to_string());
+    }
+    if *starting_stack < smallest_chip.saturating_mul(100) {
+        return Err("The starting stack must be at least 100 of the smallest chip".to_string());
+    }
+    if hours.is_nan() || *hours <= 0.0 || *hours > 24.0 {
+        return Err("The tournament must be between 0 and 24 hours long".to_string());
+    }
+    if break_every.is_some_and(|every| every > 0) {
+        if *break_minutes <= 0 {
+            return Err("Breaks must be at least a minute long".to_string());
+        }
+        if *break_minutes > DAY_MINUTES {
+            return Err("Breaks can't be longer than a day".to_string());
+        }
+    }
+
+    // work out how many levels fit in the time, counting the breaks
+    let total_minutes = (hours * 60.0) as i64;
+    let break_share = match break_every {
+        Some(every) if *every > 0 => *break_minutes as f64 / *every as f64,
+        _ => 0.0,
+    };
+    let count = ((total_minutes as f64 / (*level_minutes as f64 + break_share)) as usize)
+        .clamp(2, MAX_LEVELS);
+
+    let chip = *smallest_chip as f64;
+    let start_big = (*starting_stack as f64 / 100.0).max(2.0 * chip);
+    let end_big = (*starting_stack as f64 * *players as f64 / 20.0).max(start_big * 2.0);
+    let growth = (end_big / start_big).powf(1.0 / (count - 1) as f64);
+
+    let mut levels = vec![];
+    let mut last_small = 0;
+    for i in 0..count {
+        let target_small = start_big * growth.powi(i as i32) / 2.0;
+        let mut small = round_to_chips(target_small, *smallest_chip);
+        if small <= last_small {
+            small = next_blind(last_small, *smallest_chip);
+        }
+        last_small = small;
+        let big = small * 2;
+        let ante = match antes_from {
+            Some(from) if i + 1 >= *from => {
+                Some(round_to_chips(big as f64 / 8.0, *smallest_chip))
+            }
+            _ => None,
+        };
+        levels.push(Level::Blinds {
+            game: game.trim().to_string(),
+            betting_limit: None,
+            small,
+            big,
+            ante,
+            ante_kind: AnteKind::Everyone,
+            duration: Duration::minutes(*level_minutes),
+        });
+        if let Some(every) = break_every
+            && *every > 0
+            && (i + 1) % every == 0
+            && i + 1 < count
+        {
+            levels.push(Level::Break {
+                duration: Duration::minutes(*break_minutes),
+                color_up: vec![],
+            });
+        }
+    }
+    let structure = Structure {
+        levels,
+        chips: vec![],
+    };
+    structure.check()?;
+    Ok(structure)
+}
+
+/// The blind sizes that look like real blinds: 1, 1.5, 2, 2.5, 3, 4, 5, 6, 8
+/// times a power of ten, as long as they can be made with the smallest chip
+fn nice_blinds(smallest_chip: u32) -> impl Iterator<Item = u32> {
+    const MANTISSAS: [u32; 9] = [10, 15, 20, 25, 30, 40, 50, 60, 80];
+    (0..9u32)
+        .flat_map(|exp| MANTISSAS.iter().map(move |m| m.saturating_mul(10u32.pow(exp)) / 10))
+        .filter(move |v| *v > 0 && v % smallest_chip == 0)
+}
+
+/// the nice blind closest to `value`
+fn round_to_chips(value: f64, smallest_chip: u32) -> u32 {
+    nice_blinds(smallest_chip)
+        .min_by(|a, b| {
+            let da = (*a as f64 - value).abs();
+            let db = (*b as f64 - value).abs();
+            da.total_cmp(&db)
+        })
+        .unwrap_or(smallest_chip)
+}
+
+/// the smallest nice blind that is bigger than `value`
+fn next_blind(value: u32, smallest_chip: u32) -> u32 {
+    nice_blinds(smallest_chip)
+        .find(|v| *v > value)
+        .unwrap_or(value + smallest_chip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blinds(structure: &Structure) -> Vec<(u32, u32, Option<u32>)> {
+        structure
+            .levels
+            .iter()
+            .filter_map(|l| match l {
+                Level::Blinds { small, big, ante, .. } => Some((*small, *big, *ante)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blinds_are_rounded_to_the_chips() {
+        assert_eq!(round_to_chips(37.0, 25), 25);
+        assert_eq!(round_to_chips(140.0, 25), 150);
+        assert_eq!(round_to_chips(140.0, 100), 100);
+        assert_eq!(round_to_chips(1.0, 500), 500);
+        assert_eq!(next_blind(100, 25), 150);
+        assert_eq!(next_blind(100, 100), 200);
+        assert!(nice_blinds(25).all(|b| b % 25 == 0));
+    }
+
+    #[test]
+    fn blinds_go_up_every_level() {
+        let settings = GeneratorSettings::default();
+        let structure = generate_structure(&settings).unwrap();
+        let blinds = blinds(&structure);
+        assert!(blinds.len() >= 2);
+        assert_eq!(blinds[0], (50, 100, None));
+        for pair in blinds.windows(2) {
+            assert!(pair[1].0 > pair[0].0, "{pair:?}");
+        }
+        for (small, big, _) in &blinds {
+            assert_eq!(small % settings.smallest_chip, 0);
+            assert_eq!(*big, small * 2);
+        }
+        assert!(structure.lint().is_empty(), "{:?}", structure.lint());
+    }
+
+    #[test]
+    fn antes_and_breaks_go_where_they_are_asked_for() {
+        let settings = GeneratorSettings {
+            antes_from: Some(3),
+            break_every: Some(2),
+            ..GeneratorSettings::default()
+        };
+        let structure = generate_structure(&settings).unwrap();
+        let antes: Vec<bool> = blinds(&structure).iter().map(|(_, _, ante)| ante.is_some()).collect();
+        assert_eq!(&antes[..3], &[false, false, true]);
+        assert!(antes[2..].iter().all(|a| *a));
+        for (i, level) in structure.levels.iter().enumerate() {
+            // every third level is a break, and there isn't one at the end
+            let is_break = matches!(level, Level::Break { .. });
+            assert_eq!(is_break, i % 3 == 2, "level {}", i + 1);
+            if is_break {
+                assert_eq!(level.duration(), Duration::minutes(settings.break_minutes));
+            }
+        }
+        assert!(!matches!(structure.levels.last(), Some(Level::Break { .. })));
+
+        let no_breaks = GeneratorSettings {
+            break_every: None,
+            ..GeneratorSettings::default()
+        };
+        let structure = generate_structure(&no_breaks).unwrap();
+        assert!(!structure.levels.iter().any(|l| matches!(l, Level::Break { .. })));
+    }
+
+    #[test]
+    fn bad_settings_are_refused() {
+        let bad = [
+            GeneratorSettings {
+                game: " ".to_string(),
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                players: 1,
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                level_minutes: 0,
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                smallest_chip: 0,
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                starting_stack: 2000,
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                hours: 0.0,
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                hours: 25.0,
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                hours: f64::NAN,
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                break_minutes: -10,
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                level_minutes: i64::MAX,
+                ..GeneratorSettings::default()
+            },
+            GeneratorSettings {
+                break_minutes: i64::MAX,
+                ..GeneratorSettings::default()
+            },
+        ];
+        for settings in bad {
+            assert!(generate_structure(&settings).is_err(), "{settings:?}");
+        }
+    }
+
+    #[test]
+    fn breaks_that_cancel_out_the_levels_are_refused() {
+        // these used to make the level length 0, and the level count endless
+        let settings = GeneratorSettings {
+            level_minutes: 20,
+            break_every: Some(4),
+            break_minutes: -80,
+            ..GeneratorSettings::default()
+        };
+        assert_eq!(
+            generate_structure(&settings),
+            Err("Breaks must be at least a minute long".to_string())
+        );
+        // breaks that are never taken don't need a length
+        let settings = GeneratorSettings {
+            break_every: Some(0),
+            break_minutes: -80,
+            ..GeneratorSettings::default()
+        };
+        assert!(generate_structure(&settings).is_ok());
+        // and there are never more than a day of one minute levels
+        let settings = GeneratorSettings {
+            hours: 24.0,
+            level_minutes: 1,
+            break_every: None,
+            ..GeneratorSettings::default()
+        };
+        assert!(generate_structure(&settings).unwrap().levels.len() <= MAX_LEVELS);
+    }
+}
@@ -14,8 +14,26 @@ pub fn hydrate() {
 #[cfg(feature = "ssr")]
 pub mod backend;
 #[cfg(feature = "ssr")]
+pub mod bounties;
+#[cfg(feature = "ssr")]
+pub mod deals;
+#[cfg(feature = "ssr")]
+pub mod events;
+#[cfg(feature = "ssr")]
+pub mod generator;
+#[cfg(feature = "ssr")]
+pub mod payouts;
+#[cfg(feature = "ssr")]
 pub mod persistence;
 #[cfg(feature = "ssr")]
+pub mod players;
+#[cfg(feature = "ssr")]
+pub mod results;
+#[cfg(feature = "ssr")]
+pub mod seating;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "ssr")]
 pub mod structures;
 #[cfg(feature = "ssr")]
 pub mod timers;
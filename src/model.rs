@@ -1,10 +1,15 @@
 //! All the data that is sent between the back end and the front end
 
-use std::fmt;
+use std::{collections::HashMap, fmt, num::NonZeroUsize};
 
 pub type Duration = chrono::Duration;
 pub type DateTime = chrono::DateTime<chrono::Local>;
 pub fn now() -> DateTime {
+    // events happen at the time they were logged, so they replay the same way
+    #[cfg(feature = "ssr")]
+    if let Some(at) = crate::events::event_time() {
+        return at;
+    }
     chrono::Local::now()
 }
 
@@ -15,9 +20,15 @@ use uuid::Uuid;
 pub enum Level {
     Blinds {
         game: String,
+        /// shown before the game, when the game's name doesn't say
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        betting_limit: Option<BettingLimit>,
         small: u32,
         big: u32,
         ante: Option<u32>,
+        /// who pays the ante
+        #[serde(default)]
+        ante_kind: AnteKind,
         duration: Duration,
     },
     Limit {
@@ -34,17 +45,116 @@ pub enum Level {
         big: u32,
         duration: Duration,
     },
+    /// Limit games that take turns within the level, like HORSE. The
+    /// dealer moves on to the next game, the clock doesn't
+    Rotation {
+        /// the name of the mix, e.g. "HORSE"
+        game: String,
+        /// the games that are played in turn
+        games: Vec<String>,
+        rotate: Rotation,
+        small: u32,
+        big: u32,
+        /// for the stud games
+        ante: Option<u32>,
+        bring_in: Option<u32>,
+        duration: Duration,
+    },
     Break {
         duration: Duration,
+        /// the chip denominations taken off the table during the break
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        color_up: Vec<u32>,
     },
     Done,
 }
 
+/// How often the game changes in a rotation level
+#[derive(Copy, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub enum Rotation {
+    #[default]
+    Orbit,
+    Hands(u32),
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rotation::Orbit => write!(f, "every orbit"),
+            Rotation::Hands(1) => write!(f, "every hand"),
+            Rotation::Hands(hands) => write!(f, "every {hands} hands"),
+        }
+    }
+}
+
+/// Who pays the ante in a blinds level
+#[derive(Copy, Clone, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AnteKind {
+    /// every player at the table
+    #[default]
+    Everyone,
+    /// the big blind pays it for the table
+    BigBlind,
+    /// the button pays it for the table
+    Button,
+}
+
+impl AnteKind {
+    pub const ALL: [AnteKind; 3] = [AnteKind::Everyone, AnteKind::BigBlind, AnteKind::Button];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnteKind::Everyone => "Everyone",
+            AnteKind::BigBlind => "Big Blind",
+            AnteKind::Button => "Button",
+        }
+    }
+
+    /// the blinds and ante, e.g. "400 / 800 BBA 800"
+    fn blinds_string(&self, small: u32, big: u32, ante: Option<u32>) -> String {
+        match (ante, self) {
+            (None, _) => format!("{small} / {big}"),
+            (Some(ante), AnteKind::Everyone) => format!("{small} / {big} / {ante}"),
+            (Some(ante), AnteKind::BigBlind) => format!("{small} / {big} BBA {ante}"),
+            (Some(ante), AnteKind::Button) => format!("{small} / {big} Button Ante {ante}"),
+        }
+    }
+}
+
+/// The betting limit of a blinds level. Limit games have their own level type
+#[derive(Copy, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BettingLimit {
+    NoLimit,
+    PotLimit,
+}
+
+impl BettingLimit {
+    pub const ALL: [BettingLimit; 2] = [BettingLimit::NoLimit, BettingLimit::PotLimit];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BettingLimit::NoLimit => "No Limit",
+            BettingLimit::PotLimit => "Pot Limit",
+        }
+    }
+
+    /// the short label shown in front of the game
+    pub fn label(&self) -> &'static str {
+        match self {
+            BettingLimit::NoLimit => "NL",
+            BettingLimit::PotLimit => "PL",
+        }
+    }
+}
+
 impl Level {
     pub fn duration(&self) -> Duration {
         match self {
             Self::Blinds { duration, .. } => duration.clone(),
             Self::Limit { duration, .. } => duration.clone(),
+            Self::Rotation { duration, .. } => duration.clone(),
             Self::Stud { duration, .. } => duration.clone(),
             Self::Break { duration, .. } => duration.clone(),
             Self::Done => Duration::seconds(0),
@@ -54,32 +164,51 @@ impl Level {
         match self {
             Self::Blinds { game, .. } => game,
             Self::Limit { game, .. } => game,
+            Self::Rotation { game, .. } => game,
             Self::Stud { game, .. } => game,
             Self::Break { .. } => "",
             Self::Done => "FINISHED",
         }
     }
 
+    /// The game being played in a rotation level, after the dealer has moved
+    /// on `rotation` times
+    pub fn rotation_game(&self, rotation: usize) -> Option<&str> {
+        match self {
+            Self::Rotation { games, .. } if !games.is_empty() => {
+                Some(&games[rotation % games.len()])
+            }
+            _ => None,
+        }
+    }
+
+    /// The game with its betting limit, if the level has one, e.g. "PL Omaha"
+    pub fn game_label(&self) -> String {
+        match self {
+            Self::Blinds {
+                game,
+                betting_limit: Some(limit),
+                ..
+            } => format!("{} {game}", limit.label()),
+            _ => self.game().to_string(),
+        }
+    }
+
     // the string that we display in "next level" and in level up notifications
     pub fn short_level_string(&self, break_name: Option<&str>) -> String {
         match self {
             Level::Blinds {
-                game,
                 small,
                 big,
                 ante,
+                ante_kind,
                 ..
-            } => {
-                if let Some(ante) = ante {
-                    format!["{game} {small} / {big} / {ante}"]
-                } else {
-                    format!["{game} {small} / {big}"]
-                }
-            }
+            } => format!["{} {}", self.game_label(), ante_kind.blinds_string(*small, *big, *ante)],
             Level::Limit {
                 game, small, big, ..
             } => format!["{game} {small} / {big}  Big Bet: {}", big * 2],
-            Level::Break { duration } => {
+            Level::Rotation { game, .. } => format!["{game} {}", self.make_level_string(break_name)],
+            Level::Break { duration, .. } => {
                 let min = duration.num_minutes();
                 let label = break_name.unwrap_or("BREAK");
                 format!["{min} MINUTE {label}"]
@@ -100,16 +229,30 @@ impl Level {
     pub fn make_level_string(&self, break_name: Option<&str>) -> String {
         match self {
             Level::Blinds {
-                small, big, ante, ..
+                small,
+                big,
+                ante,
+                ante_kind,
+                ..
+            } => ante_kind.blinds_string(*small, *big, *ante),
+            Level::Limit { small, big, .. } => format!["{small} / {big}  Big Bet: {}", big * 2],
+            Level::Rotation {
+                small,
+                big,
+                ante,
+                bring_in,
+                ..
             } => {
+                let mut s = format!["{small} / {big}  Big Bet: {}", big * 2];
                 if let Some(ante) = ante {
-                    format!["{small} / {big} / {ante}"]
-                } else {
-                    format!["{small} / {big}"]
+                    s.push_str(&format!("  Ante: {ante}"));
+                }
+                if let Some(bring_in) = bring_in {
+                    s.push_str(&format!(" Bring: {bring_in}"));
                 }
+                s
             }
-            Level::Limit { small, big, .. } => format!["{small} / {big}  Big Bet: {}", big * 2],
-            Level::Break { duration } => {
+            Level::Break { duration, .. } => {
                 let min = duration.num_minutes();
                 let label = break_name.unwrap_or("BREAK");
                 format!["{min} MINUTE {label}"]
@@ -126,6 +269,523 @@ impl Level {
     }
 }
 
+/// The levels of a tournament, in order
+#[derive(Clone, PartialEq, serde::Deserialize, serde::Serialize, Debug)]
+pub struct Structure {
+    pub levels: Vec<Level>,
+    /// the chip denominations in play at the start, if they've been given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chips: Vec<u32>,
+}
+
+/// The number of a level in a structure, starting at 1. These can only be
+/// made by a `Structure`, so they always refer to one of its levels.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct LevelIndex(NonZeroUsize);
+
+impl LevelIndex {
+    /// the level number, starting at 1
+    pub fn number(&self) -> usize {
+        self.0.get()
+    }
+}
+
+impl fmt::Display for LevelIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Structure {
+    /// The first level, if there are any
+    pub fn first_level(&self) -> Option<LevelIndex> {
+        self.level_index(1)
+    }
+
+    /// The index of level `number` (starting at 1), if there is such a level
+    pub fn level_index(&self, number: usize) -> Option<LevelIndex> {
+        if number <= self.levels.len() {
+            NonZeroUsize::new(number).map(LevelIndex)
+        } else {
+            None
+        }
+    }
+
+    /// The level `delta` levels away from `l`, or `None` if that would be
+    /// before the first level or after the last one
+    pub fn offset(&self, l: LevelIndex, delta: isize) -> Option<LevelIndex> {
+        l.number()
+            .checked_add_signed(delta)
+            .and_then(|n| self.level_index(n))
+    }
+
+    pub fn get_level(&self, l: LevelIndex) -> &Level {
+        // an index from a different structure could be out of range
+        self.levels.get(l.number() - 1).unwrap_or(&Level::Done)
+    }
+
+    /// The level after `l`, which is `Level::Done` after the last one
+    pub fn next_level(&self, l: LevelIndex) -> &Level {
+        match self.offset(l, 1) {
+            Some(next) => self.get_level(next),
+            None => &Level::Done,
+        }
+    }
+
+    /// Build a structure from flattened levels, checking that each one is complete
+    pub fn from_level_defs(defs: &[LevelDef], chips: &[u32]) -> Result<Structure, String> {
+        if defs.is_empty() {
+            return Err("structure has no levels".to_string());
+        }
+        let levels = defs
+            .iter()
+            .enumerate()
+            .map(|(i, def)| def.to_level().map_err(|e| format!("level {}: {e}", i + 1)))
+            .collect::<Result<Vec<Level>, String>>()?;
+        Ok(Structure {
+            levels,
+            chips: chips.to_vec(),
+        })
+    }
+
+    /// The chips coming off at level `l`, if it's a color up break
+    pub fn color_up(&self, l: LevelIndex) -> Option<ColorUp> {
+        let Level::Break { color_up, .. } = self.get_level(l) else {
+            return None;
+        };
+        if color_up.is_empty() {
+            return None;
+        }
+        Some(ColorUp {
+            remove: color_up.clone(),
+            smallest: self.smallest_chip(l.number()),
+        })
+    }
+
+    /// The smallest chip still in play after the first `count` levels, if
+    /// the chips have been given
+    fn smallest_chip(&self, count: usize) -> Option<u32> {
+        let removed: Vec<u32> = self.levels[..count.min(self.levels.len())]
+            .iter()
+            .flat_map(|level| match level {
+                Level::Break { color_up, .. } => color_up.clone(),
+                _ => vec![],
+            })
+            .collect();
+        self.chips.iter().filter(|c| !removed.contains(c)).min().copied()
+    }
+
+    /// check a structure that didn't come from `from_level_defs`
+    pub fn check(&self) -> Result<(), String> {
+        if self.levels.is_empty() {
+            return Err("structure has no levels".to_string());
+        }
+        if self.levels.contains(&Level::Done) {
+            return Err("structure can't contain a finished level".to_string());
+        }
+        // the same as `LevelDef::to_level`, otherwise the clock runs straight
+        // through the level
+        if let Some(i) = self.levels.iter().position(|l| l.duration() < Duration::minutes(1)) {
+            return Err(format!("level {} has to be at least a minute long", i + 1));
+        }
+        Ok(())
+    }
+}
+
+/// Something that looks wrong with a structure. The structure can still be
+/// played, but it's probably a typo
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StructureWarning {
+    /// the level (starting at 1) with the problem, if the problem is with a level
+    pub level: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for StructureWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            Some(level) => write!(f, "level {level}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Structure {
+    /// look for anything that doesn't make sense in the structure
+    pub fn lint(&self) -> Vec<StructureWarning> {
+        let mut warnings = vec![];
+        let mut warn = |level: Option<usize>, message: String| {
+            warnings.push(StructureWarning { level, message })
+        };
+        if self.levels.is_empty() {
+            warn(None, "structure has no levels".to_string());
+        }
+        // the blinds of the last level of each game, so that mixed games
+        // are only compared with themselves
+        let mut last_blinds: HashMap<&str, (u32, u32)> = HashMap::new();
+        for (i, level) in self.levels.iter().enumerate() {
+            let n = Some(i + 1);
+            if level.duration() <= Duration::zero() && *level != Level::Done {
+                warn(n, "level has no time".to_string());
+            }
+            let blinds = match level {
+                Level::Blinds { small, big, .. }
+                | Level::Limit { small, big, .. }
+                | Level::Rotation { small, big, .. } => {
+                    if *small == 0 || big % small != 0 {
+                        warn(
+                            n,
+                            format!("big blind {big} is not a multiple of the small blind {small}"),
+                        );
+                    }
+                    Some((*small, *big))
+                }
+                Level::Stud {
+                    bring_in,
+                    small,
+                    big,
+                    ..
+                } => {
+                    if bring_in > small {
+                        warn(
+                            n,
+                            format!("bring in {bring_in} is more than the small bet {small}"),
+                        );
+                    }
+                    Some((*small, *big))
+                }
+                Level::Break { color_up, .. } => {
+                    if i > 0 && matches!(self.levels[i - 1], Level::Break { .. }) {
+                        warn(n, "two breaks in a row".to_string());
+                    }
+                    if !self.chips.is_empty() {
+                        for chip in color_up.iter().filter(|c| !self.chips.contains(c)) {
+                            warn(n, format!("color up removes {chip}s, which aren't one of the chips"));
+                        }
+                    }
+                    None
+                }
+                Level::Done => {
+                    warn(n, "structure can't contain a finished level".to_string());
+                    None
+                }
+            };
+            if let Some((small, _)) = blinds
+                && let Some(chip) = self.smallest_chip(i)
+                && small % chip != 0
+            {
+                warn(
+                    n,
+                    format!("small blind {small} can't be made with the smallest chip {chip}"),
+                );
+            }
+            if let Some((small, big)) = blinds
+                && let Some((last_small, last_big)) = last_blinds.insert(level.game(), (small, big))
+                && (small < last_small || big < last_big)
+            {
+                warn(
+                    n,
+                    format!(
+                        "{} blinds go down from {last_small} / {last_big} to {small} / {big}",
+                        level.game()
+                    ),
+                );
+            }
+        }
+        warnings
+    }
+}
+
+/// A flattened version of a level, used in structure files and the structure
+/// editor. Which fields are required depends on `kind`
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LevelDef {
+    /// one of "blinds", "limit", "stud", "rotation" or "break"
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub small: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub big: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ante: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bring_in: Option<u32>,
+    /// only for rotations
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub games: Vec<String>,
+    /// only for rotations, how many hands each game is played for. The game
+    /// changes every orbit if this isn't given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hands: Option<u32>,
+    /// only for blinds, everyone pays the ante if it isn't given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ante_kind: Option<AnteKind>,
+    /// only for blinds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub betting_limit: Option<BettingLimit>,
+    /// only for breaks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub color_up: Vec<u32>,
+    pub minutes: i64,
+}
+
+impl LevelDef {
+    /// a blank level of the given kind
+    pub fn new(kind: &str) -> LevelDef {
+        LevelDef {
+            kind: kind.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Change to another kind of level, clearing the fields the new kind
+    /// doesn't have so they can't stop it from being saved
+    pub fn set_kind(&mut self, kind: &str) {
+        let playing = kind != "break";
+        let blinds = kind == "blinds";
+        let rotation = kind == "rotation";
+        if !playing {
+            self.game = None;
+            self.small = None;
+            self.big = None;
+        }
+        if !(blinds || kind == "stud" || rotation) {
+            self.ante = None;
+        }
+        if !(kind == "stud" || rotation) {
+            self.bring_in = None;
+        }
+        if !rotation {
+            self.games.clear();
+            self.hands = None;
+        }
+        if !blinds {
+            self.ante_kind = None;
+            self.betting_limit = None;
+        }
+        if playing {
+            self.color_up.clear();
+        }
+        self.kind = kind.to_string();
+    }
+
+    pub fn from_level(level: &Level) -> Option<LevelDef> {
+        let def = match level {
+            Level::Blinds {
+                game,
+                betting_limit,
+                small,
+                big,
+                ante,
+                ante_kind,
+                ..
+            } => LevelDef {
+                game: Some(game.clone()),
+                betting_limit: *betting_limit,
+                small: Some(*small),
+                big: Some(*big),
+                ante: *ante,
+                ante_kind: (*ante_kind != AnteKind::Everyone).then_some(*ante_kind),
+                ..LevelDef::new("blinds")
+            },
+            Level::Limit {
+                game, small, big, ..
+            } => LevelDef {
+                game: Some(game.clone()),
+                small: Some(*small),
+                big: Some(*big),
+                ..LevelDef::new("limit")
+            },
+            Level::Stud {
+                game,
+                ante,
+                bring_in,
+                small,
+                big,
+                ..
+            } => LevelDef {
+                game: Some(game.clone()),
+                ante: Some(*ante),
+                bring_in: Some(*bring_in),
+                small: Some(*small),
+                big: Some(*big),
+                ..LevelDef::new("stud")
+            },
+            Level::Rotation {
+                game,
+                games,
+                rotate,
+                small,
+                big,
+                ante,
+                bring_in,
+                ..
+            } => LevelDef {
+                game: Some(game.clone()),
+                games: games.clone(),
+                hands: match rotate {
+                    Rotation::Orbit => None,
+                    Rotation::Hands(hands) => Some(*hands),
+                },
+                small: Some(*small),
+                big: Some(*big),
+                ante: *ante,
+                bring_in: *bring_in,
+                ..LevelDef::new("rotation")
+            },
+            Level::Break { color_up, .. } => LevelDef {
+                color_up: color_up.clone(),
+                ..LevelDef::new("break")
+            },
+            Level::Done => return None,
+        };
+        Some(LevelDef {
+            minutes: level.duration().num_minutes(),
+            ..def
+        })
+    }
+
+    /// turn the definition into a level, complaining about missing or extra fields
+    pub fn to_level(&self) -> Result<Level, String> {
+        fn required<T: Copy>(v: Option<T>, kind: &str, field: &str) -> Result<T, String> {
+            v.ok_or_else(|| format!("{kind} level is missing `{field}`"))
+        }
+        let game = || -> Result<String, String> {
+            match &self.game {
+                Some(game) if !game.trim().is_empty() => Ok(game.clone()),
+                _ => Err(format!("{} level is missing `game`", self.kind)),
+            }
+        };
+        let kind = self.kind.as_str();
+        if self.minutes <= 0 {
+            return Err(format!(
+                "{kind} level has to be at least a minute long, not {} minutes",
+                self.minutes
+            ));
+        }
+        let duration = Duration::try_minutes(self.minutes)
+            .ok_or_else(|| format!("{kind} level is too long at {} minutes", self.minutes))?;
+        if kind != "break" && !self.color_up.is_empty() {
+            return Err(format!("{kind} level can't have a `color_up`, only breaks can"));
+        }
+        if kind != "blinds" && self.ante_kind.is_some() {
+            return Err(format!("{kind} level can't have an `ante_kind`, only blinds can"));
+        }
+        if kind != "blinds" && self.betting_limit.is_some() {
+            return Err(format!("{kind} level can't have a `betting_limit`, only blinds can"));
+        }
+        if kind != "rotation" && (!self.games.is_empty() || self.hands.is_some()) {
+            return Err(format!("{kind} level can't have `games` or `hands`, only rotations can"));
+        }
+        match kind {
+            "blinds" => {
+                if self.ante_kind.is_some() && self.ante.is_none() {
+                    return Err("blinds level has an `ante_kind` but no `ante`".to_string());
+                }
+                Ok(Level::Blinds {
+                    game: game()?,
+                    betting_limit: self.betting_limit,
+                    small: required(self.small, kind, "small")?,
+                    big: required(self.big, kind, "big")?,
+                    ante: self.ante,
+                    ante_kind: self.ante_kind.unwrap_or_default(),
+                    duration,
+                })
+            }
+            "limit" => {
+                if self.ante.is_some() {
+                    return Err("limit level can't have an `ante`".to_string());
+                }
+                Ok(Level::Limit {
+                    game: game()?,
+                    small: required(self.small, kind, "small")?,
+                    big: required(self.big, kind, "big")?,
+                    duration,
+                })
+            }
+            "stud" => Ok(Level::Stud {
+                game: game()?,
+                ante: required(self.ante, kind, "ante")?,
+                bring_in: required(self.bring_in, kind, "bring_in")?,
+                small: required(self.small, kind, "small")?,
+                big: required(self.big, kind, "big")?,
+                duration,
+            }),
+            "rotation" => {
+                let games: Vec<String> = self
+                    .games
+                    .iter()
+                    .map(|g| g.trim().to_string())
+                    .filter(|g| !g.is_empty())
+                    .collect();
+                if games.is_empty() {
+                    return Err("rotation level is missing `games`".to_string());
+                }
+                let rotate = match self.hands {
+                    None => Rotation::Orbit,
+                    Some(0) => return Err("rotation level can't change game every 0 hands".to_string()),
+                    Some(hands) => Rotation::Hands(hands),
+                };
+                Ok(Level::Rotation {
+                    game: game()?,
+                    games,
+                    rotate,
+                    small: required(self.small, kind, "small")?,
+                    big: required(self.big, kind, "big")?,
+                    ante: self.ante,
+                    bring_in: self.bring_in,
+                    duration,
+                })
+            }
+            "break" => Ok(Level::Break {
+                duration,
+                color_up: self.color_up.clone(),
+            }),
+            other => Err(format!(
+                "unknown level type `{other}`, expected blinds, limit, stud, rotation or break"
+            )),
+        }
+    }
+}
+
+/// The settings for generating a blind structure
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct GeneratorSettings {
+    pub game: String,
+    pub starting_stack: u32,
+    pub players: u32,
+    /// how long the tournament should last, including breaks
+    pub hours: f64,
+    pub level_minutes: i64,
+    /// all the blinds will be a multiple of this
+    pub smallest_chip: u32,
+    /// put a break after every this many levels
+    pub break_every: Option<usize>,
+    pub break_minutes: i64,
+    /// the first level (starting at 1) that has an ante
+    pub antes_from: Option<usize>,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        GeneratorSettings {
+            game: "NLHE".to_string(),
+            starting_stack: 10000,
+            players: 10,
+            hours: 4.0,
+            level_minutes: 20,
+            smallest_chip: 25,
+            break_every: Some(4),
+            break_minutes: 10,
+            antes_from: None,
+        }
+    }
+}
+
 /// The state of a clock. Can be pause or running, each with some duration left
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum ClockState {
@@ -239,8 +899,760 @@ pub struct RoundState {
     pub cur: Level,
     pub next: Level,
     pub timer_id: Uuid,
-    pub level: usize,
+    pub level: LevelIndex,
     pub clock: ClockState,
+    /// the number of players that have registered
+    pub entrants: usize,
+    /// the number of players that haven't busted out
+    pub remaining: usize,
+    /// only known when the starting stack or buy in is set
+    pub average_stack: Option<u32>,
+    /// this is the last level to rebuy or re-enter
+    pub last_rebuy_level: bool,
+    /// only when some money has been paid in
+    pub prize_pool: Option<PrizePool>,
+    /// counting down to the scheduled start, until the clock starts
+    #[serde(default)]
+    pub starts_in: Option<ClockState>,
+    #[serde(default)]
+    pub hand_for_hand: Option<HandForHand>,
+    /// the chips coming off, when this is a color up break
+    #[serde(default)]
+    pub color_up: Option<ColorUp>,
+    /// the number of times the game has changed in rotation levels
+    #[serde(default)]
+    pub rotation: usize,
+}
+
+/// The chips that are taken off the table at a break
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct ColorUp {
+    pub remove: Vec<u32>,
+    /// the smallest chip left afterwards, if the structure has its chips
+    pub smallest: Option<u32>,
+}
+
+impl fmt::Display for ColorUp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let remove: Vec<String> = self.remove.iter().map(|c| format!("{c}s")).collect();
+        write!(f, "Color up the {}", remove.join(", "))?;
+        if let Some(smallest) = self.smallest {
+            write!(f, ", the smallest chip is now {smallest}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A player registered in a tournament
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct Player {
+    pub id: Uuid,
+    pub name: String,
+    pub seat: Option<u32>,
+    /// the table the seat is at, once seats have been drawn
+    #[serde(default)]
+    pub table: Option<u32>,
+    /// when the player busted out, if they have
+    pub busted: Option<DateTime>,
+}
+
+/// A seat at one of the tables, both starting at 1
+#[derive(PartialEq, Eq, Copy, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct Seat {
+    pub table: u32,
+    pub seat: u32,
+}
+
+impl fmt::Display for Seat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Table {} Seat {}", self.table, self.seat)
+    }
+}
+
+/// A player that has to go to a new seat
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct TableMove {
+    pub player: String,
+    /// `None` when the player didn't have a seat yet
+    pub from: Option<Seat>,
+    pub to: Seat,
+}
+
+impl fmt::Display for TableMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.from {
+            Some(from) => write!(f, "{}: {from} to {}", self.player, self.to),
+            None => write!(f, "{}: {}", self.player, self.to),
+        }
+    }
+}
+
+/// What the floor has to do after the seats were drawn or tables got
+/// unbalanced
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct SeatingChange {
+    /// the tables to break, moving everyone at them
+    pub broken_tables: Vec<u32>,
+    pub moves: Vec<TableMove>,
+}
+
+impl SeatingChange {
+    pub fn is_empty(&self) -> bool {
+        self.broken_tables.is_empty() && self.moves.is_empty()
+    }
+}
+
+/// Playing hand for hand near the bubble: every table plays one hand, then
+/// waits for the other tables to finish theirs
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct HandForHand {
+    /// the hand being played, starting at 1
+    pub hand: u32,
+    /// the tables still playing, just table 1 when the seats haven't been
+    /// drawn
+    pub tables: Vec<u32>,
+    /// the tables that have finished the hand
+    pub done: Vec<u32>,
+    /// the clock was already paused when hand for hand started, so it stays
+    /// paused when it stops
+    #[serde(default)]
+    pub clock_was_paused: bool,
+}
+
+impl HandForHand {
+    pub fn new(tables: Vec<u32>) -> HandForHand {
+        HandForHand {
+            hand: 1,
+            tables,
+            done: vec![],
+            clock_was_paused: false,
+        }
+    }
+
+    /// the tables that haven't finished the hand yet
+    pub fn waiting_on(&self) -> Vec<u32> {
+        self.tables
+            .iter()
+            .filter(|t| !self.done.contains(t))
+            .copied()
+            .collect()
+    }
+
+    /// A table finished its hand. Returns true when it was the last one and
+    /// the next hand can be dealt.
+    pub fn hand_complete(&mut self, table: u32) -> Result<bool, String> {
+        if !self.tables.contains(&table) {
+            return Err(format!("Table {table} isn't playing"));
+        }
+        if !self.done.contains(&table) {
+            self.done.push(table);
+        }
+        Ok(self.next_hand())
+    }
+
+    /// The tables changed after players busted or moved. Returns true if
+    /// every table left has finished the hand.
+    pub fn set_tables(&mut self, tables: Vec<u32>) -> bool {
+        self.done.retain(|t| tables.contains(t));
+        self.tables = tables;
+        !self.done.is_empty() && self.next_hand()
+    }
+
+    fn next_hand(&mut self) -> bool {
+        let all_done = self.waiting_on().is_empty();
+        if all_done {
+            self.hand += 1;
+            self.done.clear();
+        }
+        all_done
+    }
+}
+
+/// The ways a player can put money into a tournament
+#[derive(PartialEq, Eq, Copy, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub enum EntryKind {
+    BuyIn,
+    Rebuy,
+    AddOn,
+    ReEntry,
+}
+
+impl EntryKind {
+    pub const ALL: [EntryKind; 4] = [
+        EntryKind::BuyIn,
+        EntryKind::Rebuy,
+        EntryKind::AddOn,
+        EntryKind::ReEntry,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntryKind::BuyIn => "Buy In",
+            EntryKind::Rebuy => "Rebuy",
+            EntryKind::AddOn => "Add On",
+            EntryKind::ReEntry => "Re-entry",
+        }
+    }
+}
+
+/// What one kind of entry costs, the chips it gets you and when it closes
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct EntryRule {
+    pub price: u32,
+    pub chips: u32,
+    /// the last level (starting at 1) that this entry is allowed, or any time
+    /// if there isn't one
+    pub last_level: Option<usize>,
+}
+
+impl EntryRule {
+    pub fn is_open(&self, level: usize) -> bool {
+        self.last_level.is_none_or(|last| level <= last)
+    }
+}
+
+/// The kinds of entry that are allowed in a tournament. A kind that isn't set
+/// isn't allowed.
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct EntryRules {
+    pub buy_in: Option<EntryRule>,
+    pub rebuy: Option<EntryRule>,
+    pub add_on: Option<EntryRule>,
+    pub re_entry: Option<EntryRule>,
+}
+
+impl EntryRules {
+    pub fn get(&self, kind: EntryKind) -> Option<&EntryRule> {
+        match kind {
+            EntryKind::BuyIn => self.buy_in.as_ref(),
+            EntryKind::Rebuy => self.rebuy.as_ref(),
+            EntryKind::AddOn => self.add_on.as_ref(),
+            EntryKind::ReEntry => self.re_entry.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, kind: EntryKind) -> &mut Option<EntryRule> {
+        match kind {
+            EntryKind::BuyIn => &mut self.buy_in,
+            EntryKind::Rebuy => &mut self.rebuy,
+            EntryKind::AddOn => &mut self.add_on,
+            EntryKind::ReEntry => &mut self.re_entry,
+        }
+    }
+
+    /// true if `level` is the last one that players can rebuy or re-enter
+    pub fn is_last_rebuy_level(&self, level: usize) -> bool {
+        [&self.rebuy, &self.re_entry]
+            .into_iter()
+            .flatten()
+            .any(|rule| rule.last_level == Some(level))
+    }
+}
+
+/// Money a player put into the tournament, and the chips they got for it
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct Transaction {
+    pub player: Uuid,
+    pub kind: EntryKind,
+    pub price: u32,
+    pub chips: u32,
+    /// the level it happened in
+    pub level: usize,
+    pub time: DateTime,
+}
+
+/// A player knocking another player out
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct Elimination {
+    /// the player that busted out
+    pub player: Uuid,
+    /// the player that knocked them out
+    pub by: Uuid,
+    pub time: DateTime,
+}
+
+/// Everyone in a tournament along with what they have paid, for the players page
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct Roster {
+    pub players: Vec<Player>,
+    pub transactions: Vec<Transaction>,
+    pub rules: EntryRules,
+    pub eliminations: Vec<Elimination>,
+    /// empty unless the tournament has bounties
+    pub bounties: Vec<PlayerBounty>,
+}
+
+/// How the bounty is paid when a player is knocked out
+#[derive(PartialEq, Clone, Copy, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub enum BountyKind {
+    /// the player that knocks you out gets your whole bounty
+    #[default]
+    Fixed,
+    /// progressive knockout: the player that knocks you out gets
+    /// `cash_percent` of your bounty, and the rest is added to their own
+    Progressive { cash_percent: u32 },
+}
+
+impl BountyKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BountyKind::Fixed => "Fixed",
+            BountyKind::Progressive { .. } => "Progressive",
+        }
+    }
+}
+
+/// The bounties in a tournament. Part of every buy in, rebuy and re-entry
+/// goes to the player's bounty instead of the prize pool.
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct BountyConfig {
+    /// the part of each entry that goes to the bounty
+    pub amount: u32,
+    pub kind: BountyKind,
+}
+
+impl BountyConfig {
+    pub fn check(&self, entries: &EntryRules) -> Result<(), String> {
+        if self.amount == 0 {
+            return Err("The bounty must be more than 0".to_string());
+        }
+        if let BountyKind::Progressive { cash_percent } = self.kind
+            && cash_percent > 100
+        {
+            return Err("The bounty can't pay more than 100% in cash".to_string());
+        }
+        for kind in [EntryKind::BuyIn, EntryKind::Rebuy, EntryKind::ReEntry] {
+            if let Some(rule) = entries.get(kind)
+                && rule.price < self.amount
+            {
+                return Err(format!("The {} costs less than the bounty", kind.name()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a player's bounties stand
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct PlayerBounty {
+    pub player: Uuid,
+    /// the bounty on the player's head
+    pub bounty: u32,
+    /// the cash the player has won from knocking players out
+    pub won: u32,
+    pub knockouts: usize,
+}
+
+/// How the prize pool is split up
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub enum PayoutTable {
+    /// pays about 15% of the field
+    #[default]
+    Standard,
+    /// pays a few more places with less to the top
+    Flat,
+    WinnerTakesAll,
+    /// the percentage for each place, starting with first
+    Custom(Vec<u32>),
+}
+
+impl PayoutTable {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PayoutTable::Standard => "Standard",
+            PayoutTable::Flat => "Flat",
+            PayoutTable::WinnerTakesAll => "Winner Takes All",
+            PayoutTable::Custom(_) => "Custom",
+        }
+    }
+}
+
+/// The settings for working out the payouts
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct PayoutConfig {
+    /// the percentage of the money taken out before paying the players
+    pub rake_percent: u32,
+    /// a fixed amount taken out before paying the players
+    pub rake_fixed: u32,
+    pub table: PayoutTable,
+    /// payouts are rounded down to a multiple of this, with the leftovers going to first
+    pub round_to: u32,
+}
+
+impl Default for PayoutConfig {
+    fn default() -> Self {
+        PayoutConfig {
+            rake_percent: 0,
+            rake_fixed: 0,
+            table: PayoutTable::Standard,
+            round_to: 1,
+        }
+    }
+}
+
+impl PayoutConfig {
+    pub fn check(&self) -> Result<(), String> {
+        if self.rake_percent > 100 {
+            return Err("The rake can't be more than 100%".to_string());
+        }
+        if let PayoutTable::Custom(percentages) = &self.table
+            && percentages.iter().sum::<u32>() != 100
+        {
+            return Err("The custom payouts must add up to 100%".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The prize for one finishing place
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct Payout {
+    /// starting at 1
+    pub place: usize,
+    pub amount: u32,
+    /// the player that finished in this place, once we know
+    pub player: Option<String>,
+}
+
+/// The money in the tournament and how it's going to be paid out
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct PrizePool {
+    /// everything that was paid in
+    pub collected: u32,
+    /// the part of what was paid in that went to bounties
+    #[serde(default)]
+    pub bounties: u32,
+    pub rake: u32,
+    pub payouts: Vec<Payout>,
+}
+
+/// How to split the money that is left when the players agree to a deal
+#[derive(PartialEq, Clone, Copy, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub enum DealMethod {
+    /// the Independent Chip Model, which works out the chance of finishing
+    /// in each place from the chip counts
+    #[default]
+    Icm,
+    /// everyone gets the smallest prize left, and the rest is split by chips
+    ChipChop,
+}
+
+impl DealMethod {
+    pub const ALL: [DealMethod; 2] = [DealMethod::Icm, DealMethod::ChipChop];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DealMethod::Icm => "ICM",
+            DealMethod::ChipChop => "Chip Chop",
+        }
+    }
+}
+
+/// A player's chip count going into a deal
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct DealStack {
+    pub name: String,
+    pub chips: u32,
+}
+
+/// What is needed to start working out a deal: the players still in and the
+/// prizes for the places they are playing for, starting with first
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct DealSetup {
+    pub players: Vec<String>,
+    pub prizes: Vec<u32>,
+}
+
+/// Changes the length of some of the levels in a structure
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct DurationRule {
+    /// the first level (starting at 1) the rule is for
+    pub first: usize,
+    /// the last level the rule is for, or `None` for every level after `first`
+    pub last: Option<usize>,
+    /// the rule is for the breaks in the range instead of the playing levels
+    pub breaks: bool,
+    pub duration: Duration,
+}
+
+impl DurationRule {
+    fn applies_to(&self, number: usize, level: &Level) -> bool {
+        let is_break = matches!(level, Level::Break { .. });
+        is_break == self.breaks
+            && number >= self.first
+            && self.last.is_none_or(|last| number <= last)
+    }
+}
+
+impl fmt::Display for DurationRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = match self.last {
+            None if self.first <= 1 => None,
+            None => Some(format!("{}+", self.first)),
+            Some(last) if last == self.first => Some(last.to_string()),
+            Some(last) => Some(format!("{}-{last}", self.first)),
+        };
+        let levels = match (self.breaks, range) {
+            (true, Some(range)) => format!("breaks {range}"),
+            (true, None) => "breaks".to_string(),
+            (false, Some(range)) => range,
+            (false, None) => "all".to_string(),
+        };
+        write!(f, "{levels}: {}", self.duration.num_minutes())
+    }
+}
+
+/// Level lengths that are different from the structure. When more than one
+/// rule is for a level, the last one wins.
+///
+/// They are written one rule to a line (or separated by commas), like
+/// `10+: 12` for levels 10 and up, `3-5: 15`, `7: 20`, or `breaks 10+: 5`.
+/// Leaving out the levels makes the rule for all of them, so `breaks: 15`
+/// makes every break 15 minutes and `all: 20` makes every playing level 20.
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+#[serde(transparent)]
+pub struct DurationOverrides(pub Vec<DurationRule>);
+
+impl DurationOverrides {
+    /// the same as the old single override, which was for every level
+    /// except the breaks
+    pub fn all_levels(duration: Duration) -> DurationOverrides {
+        DurationOverrides(vec![DurationRule {
+            first: 1,
+            last: None,
+            breaks: false,
+            duration,
+        }])
+    }
+
+    /// the length of level `number` if a rule changes it
+    pub fn duration_for(&self, number: usize, level: &Level) -> Option<Duration> {
+        self.0
+            .iter()
+            .rev()
+            .find(|r| r.applies_to(number, level))
+            .map(|r| r.duration)
+    }
+
+    pub fn parse(s: &str) -> Result<DurationOverrides, String> {
+        s.split(['\n', ','])
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| parse_duration_rule(rule).map_err(|e| format!("{rule}: {e}")))
+            .collect::<Result<Vec<_>, _>>()
+            .map(DurationOverrides)
+    }
+}
+
+fn parse_duration_rule(rule: &str) -> Result<DurationRule, String> {
+    let (levels, minutes) = rule
+        .split_once(':')
+        .ok_or("needs a ':' before the minutes")?;
+    let minutes: u32 = minutes
+        .trim()
+        .parse()
+        .map_err(|_| "the minutes must be a number")?;
+    if minutes == 0 {
+        return Err("levels must be at least a minute long".to_string());
+    }
+    let levels = levels.trim();
+    let (breaks, range) = match levels.strip_prefix("breaks") {
+        Some(range) => (true, range.trim()),
+        None => (false, levels.strip_prefix("all").unwrap_or(levels).trim()),
+    };
+    let number = |n: &str| -> Result<usize, String> {
+        match n.trim().parse() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("{n} isn't a level number")),
+        }
+    };
+    let (first, last) = if range.is_empty() {
+        (1, None)
+    } else if let Some(first) = range.strip_suffix('+') {
+        (number(first)?, None)
+    } else if let Some((first, last)) = range.split_once('-') {
+        let (first, last) = (number(first)?, number(last)?);
+        if last < first {
+            return Err("the range is backwards".to_string());
+        }
+        (first, Some(last))
+    } else {
+        let n = number(range)?;
+        (n, Some(n))
+    };
+    Ok(DurationRule {
+        first,
+        last,
+        breaks,
+        duration: Duration::minutes(minutes.into()),
+    })
+}
+
+impl fmt::Display for DurationOverrides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, rule) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{rule}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A warning given some time before a level ends
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+pub struct LevelWarning {
+    /// how long before the end of the level
+    pub before: Duration,
+    pub message: String,
+    /// beep on the timer screens
+    pub sound: bool,
+    /// send it to everyone with notifications on
+    pub push: bool,
+    /// only before a break ends
+    pub breaks_only: bool,
+}
+
+impl LevelWarning {
+    pub fn applies_to(&self, level: &Level) -> bool {
+        !self.breaks_only || matches!(level, Level::Break { .. })
+    }
+}
+
+impl fmt::Display for LevelWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (minutes, seconds) = (self.before.num_minutes(), self.before.num_seconds() % 60);
+        match (minutes, seconds) {
+            (0, s) => write!(f, "{s}s")?,
+            (m, 0) => write!(f, "{m}m")?,
+            (m, s) => write!(f, "{m}m{s}s")?,
+        }
+        for (on, word) in [
+            (self.breaks_only, "breaks"),
+            (self.sound, "sound"),
+            (self.push, "push"),
+        ] {
+            if on {
+                write!(f, " {word}")?;
+            }
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The warnings given before a level ends, one to a line, like
+/// `5m push: Five minutes left` or `10s sound: Ten seconds`. After the time
+/// come the options: `breaks` to only warn before breaks end, `sound` to beep
+/// on the timer screens and `push` to send a notification.
+#[derive(PartialEq, Clone, serde::Deserialize, serde::Serialize, Debug)]
+#[serde(transparent)]
+pub struct Warnings(pub Vec<LevelWarning>);
+
+impl Default for Warnings {
+    /// the one minute warning before a break ends that the timer has always
+    /// given
+    fn default() -> Self {
+        Warnings(vec![LevelWarning {
+            before: Duration::minutes(1),
+            message: "Take your seats! One minute till CIA".to_string(),
+            sound: false,
+            push: true,
+            breaks_only: true,
+        }])
+    }
+}
+
+impl Warnings {
+    pub fn parse(s: &str) -> Result<Warnings, String> {
+        s.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| parse_warning(line).map_err(|e| format!("{line}: {e}")))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Warnings)
+    }
+}
+
+fn parse_warning(line: &str) -> Result<LevelWarning, String> {
+    let (when, message) = line
+        .split_once(':')
+        .ok_or("needs a ':' before the message")?;
+    let message = message.trim();
+    if message.is_empty() {
+        return Err("the message is missing".to_string());
+    }
+    let mut words = when.split_whitespace();
+    let before = words
+        .next()
+        .and_then(parse_warning_time)
+        .ok_or("start with the time before the end, like 5m, 30s or 1m30s")?;
+    let mut warning = LevelWarning {
+        before,
+        message: message.to_string(),
+        sound: false,
+        push: false,
+        breaks_only: false,
+    };
+    for word in words {
+        match word {
+            "breaks" => warning.breaks_only = true,
+            "sound" => warning.sound = true,
+            "push" => warning.push = true,
+            _ => return Err(format!("{word} isn't breaks, sound or push")),
+        }
+    }
+    Ok(warning)
+}
+
+/// `5m`, `30s` or `1m30s`
+fn parse_warning_time(s: &str) -> Option<Duration> {
+    let (minutes, seconds) = match s.split_once('m') {
+        Some((minutes, seconds)) => (minutes.parse::<u32>().ok()?, seconds),
+        None => (0, s),
+    };
+    let seconds: u32 = match seconds {
+        "" => 0,
+        s => s.strip_suffix('s')?.parse().ok()?,
+    };
+    let before = Duration::minutes(minutes.into()) + Duration::seconds(seconds.into());
+    (before > Duration::zero()).then_some(before)
+}
+
+impl fmt::Display for Warnings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, warning) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The tournament settings that can be changed on the settings page
+#[derive(PartialEq, Clone, Default, serde::Deserialize, serde::Serialize, Debug)]
+pub struct TournamentConfig {
+    /// level lengths that are different from the structure
+    #[serde(default)]
+    pub durations: DurationOverrides,
+    /// the chips each player starts with if there is no buy in, used to work
+    /// out the average stack
+    pub starting_stack: Option<u32>,
+    #[serde(default)]
+    pub entries: EntryRules,
+    #[serde(default)]
+    pub payouts: PayoutConfig,
+    /// the most players at a table, which turns on drawing seats and
+    /// balancing the tables
+    #[serde(default)]
+    pub table_size: Option<u32>,
+    #[serde(default)]
+    pub bounty: Option<BountyConfig>,
+    /// the warnings given before levels end
+    #[serde(default)]
+    pub warnings: Warnings,
 }
 
 /// The state of the timer component
@@ -248,7 +1660,10 @@ pub struct RoundState {
 pub enum TimerCompState {
     Loading,
     NoTournament,
-    Running { subscribed: bool, state: RoundState },
+    Running {
+        subscribed: bool,
+        state: Box<RoundState>,
+    },
     Error(String),
 }
 
@@ -257,6 +1672,8 @@ pub enum TimerCompState {
 pub enum DeviceMessage {
     NewState(TimerCompState),
     Beep,
+    /// players that have to change seats
+    Seating(SeatingChange),
 }
 
 /// a message sent from the app to the backend
@@ -267,6 +1684,26 @@ pub enum Command {
     NextLevel,
     PrevLevel,
     Terminate,
+    RegisterPlayer { name: String, seat: Option<u32> },
+    UnregisterPlayer(Uuid),
+    /// a player busting out, and who knocked them out if anyone
+    BustPlayer { player: Uuid, by: Option<Uuid> },
+    /// a player paying for a rebuy, add on, etc.
+    AddEntry { player: Uuid, kind: EntryKind },
+    /// randomly seat everyone still playing
+    DrawSeats,
+    /// set the time left in the current level
+    SetTime(Duration),
+    /// add time to the current level, or take it away if it's negative
+    AddTime(Duration),
+    /// stop the clock and play one hand at a time at every table
+    StartHandForHand,
+    /// go back to the clock
+    StopHandForHand,
+    /// the table has finished its hand
+    HandComplete(u32),
+    /// the dealer moves on to the next game of a rotation level
+    NextGame,
 }
 
 /// Query-string parameters carried by timer/settings/qr/manifest URLs
@@ -0,0 +1,158 @@
+//! Works out the prize pool from what the players have paid in, and how much
+//! each finishing place gets.
+
+use crate::bounties::bounty_pool;
+use crate::model::*;
+use crate::players::Players;
+
+/// The percentage for each paid place, starting with first, for a field of
+/// `entrants` players
+pub fn percentages(table: &PayoutTable, entrants: usize) -> Vec<u32> {
+    match table {
+        PayoutTable::Standard => match entrants {
+            0..=3 => vec![100],
+            4..=6 => vec![65, 35],
+            7..=10 => vec![50, 30, 20],
+            11..=15 => vec![45, 27, 18, 10],
+            16..=20 => vec![40, 25, 16, 11, 8],
+            21..=30 => vec![35, 22, 15, 11, 9, 8],
+            31..=50 => vec![30, 20, 14, 10, 8, 7, 6, 5],
+            _ => vec![27, 18, 13, 10, 8, 7, 6, 5, 3, 3],
+        },
+        PayoutTable::Flat => match entrants {
+            0..=3 => vec![100],
+            4..=6 => vec![60, 40],
+            7..=10 => vec![45, 32, 23],
+            11..=20 => vec![38, 26, 20, 16],
+            _ => vec![30, 22, 17, 13, 10, 8],
+        },
+        PayoutTable::WinnerTakesAll => vec![100],
+        PayoutTable::Custom(percentages) => percentages.clone(),
+    }
+}
+
+/// Split `pool` by `percentages`, rounding each payout down to a multiple of
+/// `round_to`. Whatever is left over from the rounding goes to first place.
+pub fn split(pool: u32, percentages: &[u32], round_to: u32) -> Vec<u32> {
+    let total: u64 = percentages.iter().map(|p| *p as u64).sum();
+    if total == 0 {
+        return vec![];
+    }
+    let round_to = round_to.max(1);
+    let mut amounts: Vec<u32> = percentages
+        .iter()
+        .map(|p| {
+            let amount = (pool as u64 * *p as u64 / total) as u32;
+            amount - amount % round_to
+        })
+        .collect();
+    let leftover = pool - amounts.iter().sum::<u32>();
+    amounts[0] += leftover;
+    amounts
+}
+
+/// The prize pool for the tournament, or `None` if nobody has paid anything.
+/// The bounties come out of the money paid in before the rake.
+pub fn prize_pool(
+    players: &Players,
+    config: &PayoutConfig,
+    bounty: Option<&BountyConfig>,
+) -> Option<PrizePool> {
+    let collected: u32 = players.transactions().iter().map(|t| t.price).sum();
+    if collected == 0 {
+        return None;
+    }
+    let bounties = bounty.map(|b| bounty_pool(players, b)).unwrap_or(0);
+    let pool = collected - bounties;
+    let rake = (pool as u64 * config.rake_percent.min(100) as u64 / 100) as u32;
+    let rake = (rake + config.rake_fixed).min(pool);
+
+    // never pay more places than there are players
+    let mut percentages = percentages(&config.table, players.entrants());
+    percentages.truncate(players.entrants().max(1));
+    let finishers = players.finishers();
+    let payouts = split(pool - rake, &percentages, config.round_to)
+        .into_iter()
+        .enumerate()
+        .map(|(i, amount)| Payout {
+            place: i + 1,
+            amount,
+            player: finishers
+                .iter()
+                .find(|(place, _)| *place == i + 1)
+                .map(|(_, p)| p.name.clone()),
+        })
+        .collect();
+    Some(PrizePool {
+        collected,
+        bounties,
+        rake,
+        payouts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_ladders_add_up() {
+        for table in [PayoutTable::Standard, PayoutTable::Flat] {
+            for entrants in 0..200 {
+                let percentages = percentages(&table, entrants);
+                assert_eq!(percentages.iter().sum::<u32>(), 100, "{table:?} {entrants}");
+                assert!(percentages.is_sorted_by(|a, b| a >= b), "{table:?} {entrants}");
+            }
+        }
+    }
+
+    #[test]
+    fn rounding_goes_to_first() {
+        assert_eq!(split(1000, &[50, 30, 20], 1), vec![500, 300, 200]);
+        assert_eq!(split(1010, &[50, 30, 20], 5), vec![510, 300, 200]);
+        assert_eq!(split(999, &[50, 30, 20], 10), vec![519, 290, 190]);
+        assert_eq!(split(100, &[], 1), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn payouts_follow_the_busts() {
+        let rule = Some(EntryRule {
+            price: 20,
+            chips: 1000,
+            last_level: None,
+        });
+        let rules = EntryRules {
+            buy_in: rule,
+            ..Default::default()
+        };
+        let mut players = Players::default();
+        let ids: Vec<_> = ["A", "B", "C", "D", "E", "F", "G"]
+            .into_iter()
+            .map(|name| players.enter(name, None, &rules, 1).unwrap())
+            .collect();
+        let config = PayoutConfig {
+            rake_percent: 10,
+            rake_fixed: 2,
+            ..Default::default()
+        };
+        let pool = prize_pool(&players, &config, None).unwrap();
+        assert_eq!(pool.collected, 140);
+        assert_eq!(pool.rake, 16);
+        let amounts: Vec<u32> = pool.payouts.iter().map(|p| p.amount).collect();
+        assert_eq!(amounts, vec![63, 37, 24]);
+
+        for id in &ids[..4] {
+            players.bust(*id).unwrap();
+        }
+        let pool = prize_pool(&players, &config, None).unwrap();
+        assert_eq!(pool.payouts[2].player, None);
+        // make sure the last few bust in order
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        players.bust(ids[4]).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        players.bust(ids[5]).unwrap();
+        let pool = prize_pool(&players, &config, None).unwrap();
+        let names: Vec<_> = pool.payouts.iter().map(|p| p.player.clone()).collect();
+        assert_eq!(names, vec![Some("G".to_string()), Some("F".to_string()), Some("E".to_string())]);
+    }
+}
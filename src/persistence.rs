@@ -1,27 +1,45 @@
-//! Dont use any kind of back end database to keep things lightweight.
+//! Dont use any kind of back end database by default to keep things lightweight.
 //! However, when updating the server code the container needs to bounce.
 //! This module supports reloading the currently running poker timers
 //! when the new server comes up so we don't interrupt any games.
+//!
+//! The running timers are also snapshotted every so often and whenever one of
+//! them changes, so that a crash or a power loss doesn't lose them either.
+//!
+//! Where all of that goes is up to the `Storage`: JSON files in `./storage`,
+//! or with the `sqlite` feature, a database that also keeps every tournament
+//! after it ends.
 
 use std::{
     collections::HashMap,
     fs,
     io::{BufReader, Write},
+    path::{Path, PathBuf},
 };
 
-use log::error;
+use log::{error, info};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use tokio::sync::Notify;
 use uuid::Uuid;
 
 use crate::{
     backend::Subscription,
+    events::{EVENTS_DIR, Log, LogEntry, append_to, compact_in, last_ended_in, read_logs, replay_logs},
     model::*,
+    players::Players,
     timers::{Timer, Tournament},
 };
 
 /// This is the format that the tournaments are stored on disk.
-/// It must remain bacward compatible to the previously running version
-#[derive(serde::Serialize, serde::Deserialize)]
+/// It must remain bacward compatible to the previously running version: a new
+/// field needs a default, and anything else needs a new `STORED_VERSION` and a
+/// migration from the one before. See `migrate`.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+// the derived implementations are only used once the record is migrated
+#[serde(remote = "Self")]
 pub struct StoredTournament {
+    pub version: u64,
     pub timer_id: Uuid,
     pub created: DateTime,
     pub structure_name: String,
@@ -31,67 +49,616 @@ pub struct StoredTournament {
     pub clock_paused: bool,
     pub clock_remaining: Duration,
     pub clock_asof: DateTime,
-    pub duration_override: Option<Duration>,
     pub subscriptions: HashMap<Uuid, Subscription>,
+    /// The structure being played, so that one off structures survive a restart
+    #[serde(default)]
+    pub structure: Option<Structure>,
+    #[serde(default)]
+    pub starting_stack: Option<u32>,
+    #[serde(default)]
+    pub entries: EntryRules,
+    #[serde(default)]
+    pub payouts: PayoutConfig,
+    #[serde(default)]
+    pub players: Players,
+    #[serde(default)]
+    pub table_size: Option<u32>,
+    #[serde(default)]
+    pub bounty: Option<BountyConfig>,
+    #[serde(default)]
+    pub durations: DurationOverrides,
+    #[serde(default)]
+    pub starts_at: Option<DateTime>,
+    /// tournaments saved before warnings could be changed get the old one
+    /// minute warning before breaks end
+    #[serde(default)]
+    pub warnings: Warnings,
+    #[serde(default)]
+    pub hand_for_hand: Option<HandForHand>,
+    #[serde(default)]
+    pub rotation: usize,
 }
 
 impl From<&Tournament> for StoredTournament {
     fn from(value: &Tournament) -> Self {
         StoredTournament {
+            version: STORED_VERSION,
             timer_id: value.timer_id,
             created: value.created,
             structure_name: value.structure_name.clone(),
-            level: value.level,
+            level: value.level.number(),
             clock_paused: value.clock_state.is_paused(),
             clock_remaining: value.clock_state.remaining(),
             clock_asof: now(),
-            duration_override: value.duration_override,
             subscriptions: value.subscriptions.clone(),
+            structure: Some(value.structure.as_ref().clone()),
+            starting_stack: value.starting_stack,
+            entries: value.entries.clone(),
+            payouts: value.payouts.clone(),
+            players: value.players.clone(),
+            table_size: value.table_size,
+            bounty: value.bounty.clone(),
+            durations: value.durations.clone(),
+            starts_at: value.starts_at,
+            warnings: value.warnings.clone(),
+            hand_for_hand: value.hand_for_hand.clone(),
+            rotation: value.rotation,
         }
     }
 }
 
+impl serde::Serialize for StoredTournament {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredTournament::serialize(self, serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for StoredTournament {
+    /// Records written by older versions are migrated first
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let value = migrate(Value::deserialize(deserializer)?).map_err(D::Error::custom)?;
+        StoredTournament::deserialize(value).map_err(D::Error::custom)
+    }
+}
+
+/// The version of `StoredTournament` written by this server. Records from
+/// before there were versions are version 0.
+pub const STORED_VERSION: u64 = 1;
+
+/// Turns a record of one version into the next
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` turns a version `n` record into a version `n + 1` one
+const MIGRATIONS: [Migration; STORED_VERSION as usize] = [durations_from_override];
+
+/// Bring a stored tournament from any older version up to `STORED_VERSION`
+fn migrate(mut value: Value) -> Result<Value, String> {
+    if !value.is_object() {
+        return Err("a stored tournament has to be an object".to_string());
+    }
+    let version = match value.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| format!("bad version {version}"))?,
+    };
+    if version > STORED_VERSION {
+        return Err(format!(
+            "version {version} is newer than this server, which is at {STORED_VERSION}"
+        ));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&mut value)?;
+    }
+    value["version"] = STORED_VERSION.into();
+    Ok(value)
+}
+
+/// 0 to 1: before there were per level overrides, there was a single override
+/// for every level but the breaks
+fn durations_from_override(value: &mut Value) -> Result<(), String> {
+    let Some(old) = value.as_object_mut().and_then(|v| v.remove("duration_override")) else {
+        return Ok(());
+    };
+    let has_rules = value["durations"].as_array().is_some_and(|rules| !rules.is_empty());
+    if !old.is_null() && !has_rules {
+        let duration: Duration = serde_json::from_value(old).map_err(|e| e.to_string())?;
+        value["durations"] =
+            serde_json::to_value(DurationOverrides::all_levels(duration)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Where the stored tournaments and log entries that can't be read are kept,
+/// one to a line, so that they can be fixed by hand
+pub const DEAD_LETTERS: &str = "./storage/dead-letters.jsonl";
+
+/// Read the stored tournaments (or log entries) in `records`. The ones that
+/// can't be read are added to `dead_letters` instead of stopping the rest
+/// from loading.
+pub(crate) fn read_records<T: serde::de::DeserializeOwned>(
+    records: Vec<Value>,
+    source: &str,
+    dead_letters: &Path,
+) -> Vec<T> {
+    let mut read = vec![];
+    for record in records {
+        match serde_json::from_value(record.clone()) {
+            Ok(stored) => read.push(stored),
+            Err(e) => {
+                error!("Quarantining a record from {source} that can't be read: {e}");
+                if let Err(e) = quarantine(dead_letters, source, &e.to_string(), record) {
+                    error!("Couldn't quarantine it in {}: {e}", dead_letters.display());
+                }
+            }
+        }
+    }
+    read
+}
+
+fn quarantine(
+    dead_letters: &Path,
+    source: &str,
+    error: &str,
+    record: Value,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = dead_letters.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_vec(&serde_json::json!({
+        "at": now(),
+        "source": source,
+        "error": error,
+        "record": record,
+    }))?;
+    line.push(b'\n');
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dead_letters)?;
+    file.write_all(&line)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// Where the tournaments are kept between runs of the server
+pub trait Storage: Send + Sync {
+    /// Keep the running tournaments, to load when the server starts
+    fn save_running(&self, timers: &[StoredTournament]) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// The running tournaments that were saved last, if any were
+    fn load_running(&self) -> Result<Option<Vec<StoredTournament>>, Box<dyn std::error::Error>>;
+
+    /// Add an entry to the end of a timer's log. `tournament` is how things
+    /// are after it.
+    fn log_event(
+        &self,
+        timer_id: Uuid,
+        entry: &LogEntry,
+        tournament: &Tournament,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// A timer's tournament is over, and this is how it ended
+    fn end(&self, timer_id: Uuid, last: &StoredTournament) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// The logs to replay when the server starts, for every timer that has
+    /// one
+    fn logs(&self) -> Result<Vec<Log>, Box<dyn std::error::Error>>;
+
+    /// How the last tournament on a timer ended, if one has
+    fn last_ended(&self, timer_id: Uuid) -> Result<Option<StoredTournament>, Box<dyn std::error::Error>>;
+}
+
+/// Snapshots in `./storage/snapshots` and a log file for each timer in
+/// `./storage/events`. A log is compacted down to the final state once the
+/// tournament ends.
+pub struct JsonStorage;
+
+impl Storage for JsonStorage {
+    fn save_running(&self, timers: &[StoredTournament]) -> Result<(), Box<dyn std::error::Error>> {
+        write_snapshot(Path::new(SNAPSHOT_DIR), timers)?;
+        Ok(())
+    }
+
+    fn load_running(&self) -> Result<Option<Vec<StoredTournament>>, Box<dyn std::error::Error>> {
+        Ok(newest_snapshot(Path::new(SNAPSHOT_DIR), Path::new(DEAD_LETTERS)))
+    }
+
+    fn log_event(
+        &self,
+        timer_id: Uuid,
+        entry: &LogEntry,
+        _tournament: &Tournament,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        append_to(Path::new(EVENTS_DIR), timer_id, entry)
+    }
+
+    fn end(&self, timer_id: Uuid, last: &StoredTournament) -> Result<(), Box<dyn std::error::Error>> {
+        compact_in(Path::new(EVENTS_DIR), timer_id, last)
+    }
+
+    fn logs(&self) -> Result<Vec<Log>, Box<dyn std::error::Error>> {
+        Ok(read_logs(Path::new(EVENTS_DIR), Path::new(DEAD_LETTERS))?)
+    }
+
+    fn last_ended(&self, timer_id: Uuid) -> Result<Option<StoredTournament>, Box<dyn std::error::Error>> {
+        Ok(last_ended_in(Path::new(EVENTS_DIR), timer_id)?)
+    }
+}
+
+static STORAGE: Lazy<Box<dyn Storage>> = Lazy::new(|| {
+    #[cfg(feature = "sqlite")]
+    {
+        use crate::sqlite::{DATABASE, SqliteStorage};
+        match SqliteStorage::open(Path::new(DATABASE)) {
+            Ok(db) => return Box::new(db),
+            Err(e) => error!("Couldn't open {DATABASE}, using JSON files instead: {e}"),
+        }
+    }
+    Box::new(JsonStorage)
+});
+
+/// Where the tournaments are kept
+pub fn storage() -> &'static dyn Storage {
+    STORAGE.as_ref()
+}
+
+/// Where the snapshots of the running timers are written
+pub const SNAPSHOT_DIR: &str = "./storage/snapshots";
+
+/// How many snapshots are kept, in case the newest one can't be read
+const KEEP_SNAPSHOTS: usize = 3;
+
+/// How often the running timers are snapshotted when nothing changes, unless
+/// `SNAPSHOT_SECONDS` says otherwise
+const DEFAULT_SNAPSHOT_SECONDS: u64 = 60;
+
+/// Woken up when a timer changes and should be snapshotted
+static SNAPSHOT_NEEDED: Lazy<Notify> = Lazy::new(Notify::new);
+
 /// Save the running tournaments that a less than a week old
 pub fn save_running() -> Result<(), Box<dyn std::error::Error>> {
     let mut timers: Vec<StoredTournament> = vec![];
     Timer::for_running_timers(|t| {
         timers.push(StoredTournament::from(t.tournament.as_ref().unwrap()))
     });
+    storage().save_running(&timers)
+}
+
+/// Snapshot the running timers soon, because one of them changed
+pub fn snapshot_soon() {
+    SNAPSHOT_NEEDED.notify_one();
+}
+
+/// How often to snapshot the running timers, from the `SNAPSHOT_SECONDS`
+/// environment variable
+pub fn snapshot_interval() -> std::time::Duration {
+    let seconds = std::env::var("SNAPSHOT_SECONDS")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(DEFAULT_SNAPSHOT_SECONDS);
+    std::time::Duration::from_secs(seconds)
+}
+
+/// Snapshot the running timers every `interval`, and whenever one of them
+/// changes. Runs forever.
+pub async fn snapshot_periodically(interval: std::time::Duration) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {},
+            _ = SNAPSHOT_NEEDED.notified() => {},
+        }
+        let saved = tokio::task::spawn_blocking(|| save_running().map_err(|e| e.to_string())).await;
+        match saved {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Couldn't snapshot running timers: {e}"),
+            Err(e) => error!("Snapshot task failed: {e}"),
+        }
+    }
+}
+
+/// Write the timers to a new snapshot in `dir` so that a crash part way
+/// through never leaves a half written file behind: the snapshot is written to
+/// a temporary file, flushed to disk and then renamed. Only the newest few
+/// snapshots are kept.
+fn write_snapshot(
+    dir: &Path,
+    timers: &[StoredTournament],
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("timers-{}.json", now().timestamp_millis()));
+    let temp = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&temp)?;
+    file.write_all(&serde_json::to_vec(timers)?)?;
+    file.sync_all()?;
+    fs::rename(&temp, &path)?;
+    // make the rename itself survive a power loss
+    fs::File::open(dir)?.sync_all()?;
 
-    if timers.len() > 0 {
-        fs::OpenOptions::new()
-            .write(true)
-            .create_new(true)
-            .open("./storage/timers.json")?
-            .write_all(&serde_json::to_vec(&timers)?)?;
+    for old in snapshots(dir)?.into_iter().skip(KEEP_SNAPSHOTS) {
+        fs::remove_file(old)?;
     }
-    Ok(())
+    Ok(path)
 }
 
-pub fn load_saved() -> Result<(), Box<dyn std::error::Error>> {
-    let path = std::path::Path::new("./storage/timers.json");
-    if !path.exists() {
-        return Ok(());
+/// The snapshots in `dir`, newest first
+fn snapshots(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut snapshots: Vec<(i64, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let millis = name
+                .strip_prefix("timers-")?
+                .strip_suffix(".json")?
+                .parse()
+                .ok()?;
+            Some((millis, path))
+        })
+        .collect();
+    snapshots.sort_by_key(|(millis, _)| std::cmp::Reverse(*millis));
+    Ok(snapshots.into_iter().map(|(_, path)| path).collect())
+}
+
+/// The timers in a snapshot. Only the timers that can't be read are
+/// quarantined, but a file that isn't a list of them is an error.
+fn read_timers(
+    path: &Path,
+    dead_letters: &Path,
+) -> Result<Vec<StoredTournament>, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    let records: Vec<Value> = serde_json::from_reader(BufReader::new(file))?;
+    Ok(read_records(records, &path.display().to_string(), dead_letters))
+}
+
+/// The timers in the newest snapshot in `dir` that can be read, skipping any
+/// that can't
+fn newest_snapshot(dir: &Path, dead_letters: &Path) -> Option<Vec<StoredTournament>> {
+    if !dir.exists() {
+        return None;
     }
-    let tournaments =
-        serde_json::from_reader::<_, Vec<StoredTournament>>(BufReader::new(fs::File::open(path)?));
-    match tournaments {
+    let paths = match snapshots(dir) {
+        Ok(paths) => paths,
         Err(e) => {
-            error!("bad timers.json file, punting: {e}");
+            error!("Couldn't list snapshots in {}: {e}", dir.display());
+            return None;
         }
-        Ok(tournaments) => {
-            for t in tournaments.into_iter() {
-                let timer_id = t.timer_id;
-                let mut timer = Timer::get_mut(timer_id);
-                timer.make_tournament_from_storage(t)?;
+    };
+    for path in paths {
+        match read_timers(&path, dead_letters) {
+            Ok(timers) => {
+                info!("Loading running timers from {}", path.display());
+                return Some(timers);
             }
+            Err(e) => error!("bad snapshot {}, trying an older one: {e}", path.display()),
         }
     }
-    let backpath = std::path::Path::new("./storage/timers.json.backup");
-    if backpath.exists() {
-        fs::remove_file(backpath)?;
-    }
-    fs::rename(path, backpath)?;
+    None
+}
 
+pub fn load_saved() -> Result<(), Box<dyn std::error::Error>> {
+    // written at shutdown by the versions from before there were snapshots
+    let path = Path::new("./storage/timers.json");
+    let tournaments = if path.exists() {
+        let tournaments = match read_timers(path, Path::new(DEAD_LETTERS)) {
+            Ok(tournaments) => Some(tournaments),
+            Err(e) => {
+                error!("bad timers.json file, it is kept in timers.json.backup: {e}");
+                None
+            }
+        };
+        let backpath = Path::new("./storage/timers.json.backup");
+        if backpath.exists() {
+            fs::remove_file(backpath)?;
+        }
+        fs::rename(path, backpath)?;
+        tournaments
+    } else {
+        // the logs are exact, the snapshot is only for timers without one
+        let replayed = replay_logs(storage().logs()?);
+        storage()
+            .load_running()?
+            .map(|timers| timers.into_iter().filter(|t| !replayed.contains(&t.timer_id)).collect())
+    };
+    for t in tournaments.unwrap_or_default() {
+        let timer_id = t.timer_id;
+        let mut timer = Timer::get_mut(timer_id);
+        timer.make_tournament_from_storage(t)?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORED: &str = r#"{
+        "timer_id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+        "created": "2025-03-01T19:00:00-05:00",
+        "structure_name": "Nightly NLHE",
+        "level": 3,
+        "clock_paused": true,
+        "clock_remaining": [600, 0],
+        "clock_asof": "2025-03-01T20:00:00-05:00",
+        "subscriptions": {}
+    }"#;
+
+    /// The first version that was saved
+    const ORIGINAL: &str = r#"{
+        "timer_id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+        "created": "2025-03-01T19:00:00-05:00",
+        "structure_name": "Nightly NLHE",
+        "level": 2,
+        "clock_paused": false,
+        "clock_remaining": [300, 0],
+        "clock_asof": "2025-03-01T19:30:00-05:00",
+        "duration_override": null,
+        "subscriptions": {}
+    }"#;
+
+    /// With the players, but still a single duration override
+    const WITH_PLAYERS: &str = r#"{
+        "timer_id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+        "created": "2025-03-01T19:00:00-05:00",
+        "structure_name": "Nightly NLHE",
+        "level": 4,
+        "clock_paused": true,
+        "clock_remaining": [600, 0],
+        "clock_asof": "2025-03-01T20:00:00-05:00",
+        "duration_override": [1200, 0],
+        "subscriptions": {},
+        "structure": null,
+        "starting_stack": 10000,
+        "entries": {"buy_in": null, "rebuy": null, "add_on": null, "re_entry": null},
+        "players": {
+            "players": [
+                {"id": "0b9e4c3e-5a8f-4f5e-9a55-6c2a3bbf1d01", "name": "Alice", "seat": 1, "busted": null},
+                {"id": "0b9e4c3e-5a8f-4f5e-9a55-6c2a3bbf1d02", "name": "Bob", "seat": 2,
+                 "busted": "2025-03-01T19:45:00-05:00"}
+            ],
+            "transactions": [],
+            "eliminations": []
+        },
+        "table_size": 9,
+        "bounty": null
+    }"#;
+
+    /// The last one from before there were versions
+    const UNVERSIONED: &str = r#"{
+        "timer_id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+        "created": "2025-03-01T19:00:00-05:00",
+        "structure_name": "Nightly NLHE",
+        "level": 10,
+        "clock_paused": true,
+        "clock_remaining": [720, 0],
+        "clock_asof": "2025-03-01T23:00:00-05:00",
+        "subscriptions": {},
+        "structure": null,
+        "starting_stack": null,
+        "table_size": null,
+        "bounty": null,
+        "durations": [{"first": 10, "last": null, "breaks": false, "duration": [720, 0]}],
+        "starts_at": null,
+        "warnings": [],
+        "hand_for_hand": null,
+        "rotation": 2
+    }"#;
+
+    #[test]
+    fn every_old_format_loads() {
+        let original: StoredTournament = serde_json::from_str(ORIGINAL).unwrap();
+        assert_eq!(original.version, STORED_VERSION);
+        assert_eq!(original.level, 2);
+        assert!(original.durations.0.is_empty());
+
+        let with_players: StoredTournament = serde_json::from_str(WITH_PLAYERS).unwrap();
+        assert_eq!(with_players.players.entrants(), 2);
+        assert_eq!(with_players.players.remaining(), 1);
+        assert_eq!(
+            with_players.durations,
+            DurationOverrides::all_levels(Duration::minutes(20))
+        );
+
+        let unversioned: StoredTournament = serde_json::from_str(UNVERSIONED).unwrap();
+        assert_eq!(unversioned.rotation, 2);
+        assert_eq!(unversioned.durations.0[0].first, 10);
+        assert!(unversioned.warnings.0.is_empty());
+
+        // and they are written back as the current version
+        for stored in [original, with_players, unversioned] {
+            let json = serde_json::to_value(&stored).unwrap();
+            assert_eq!(json["version"], STORED_VERSION);
+            let again: StoredTournament = serde_json::from_value(json).unwrap();
+            assert_eq!(again.durations, stored.durations);
+        }
+    }
+
+    #[test]
+    fn unreadable_tournaments_are_quarantined() {
+        let dir = std::env::temp_dir().join(format!("pokertimer-{}", Uuid::new_v4()));
+        let dead_letters = dir.join("dead-letters.jsonl");
+        let mut newer: Value = serde_json::from_str(STORED).unwrap();
+        newer["version"] = (STORED_VERSION + 1).into();
+        let mut mangled: Value = serde_json::from_str(STORED).unwrap();
+        mangled["level"] = "three".into();
+        let records = vec![serde_json::from_str(STORED).unwrap(), newer, mangled];
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("timers.json");
+        fs::write(&path, serde_json::to_vec(&records).unwrap()).unwrap();
+
+        // the good one still loads
+        let timers = read_timers(&path, &dead_letters).unwrap();
+        assert_eq!(timers.len(), 1);
+        let quarantined: Vec<Value> = fs::read_to_string(&dead_letters)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(quarantined.len(), 2);
+        assert_eq!(quarantined[0]["record"], records[1]);
+        assert!(quarantined[0]["error"].as_str().unwrap().contains("newer"));
+        assert_eq!(quarantined[1]["record"], records[2]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_newest_good_snapshot_is_loaded() {
+        let dir = std::env::temp_dir().join(format!("pokertimer-{}", Uuid::new_v4()));
+        let dead_letters = dir.join("dead-letters.jsonl");
+        assert!(newest_snapshot(&dir, &dead_letters).is_none());
+        let stored: StoredTournament = serde_json::from_str(STORED).unwrap();
+        let good = write_snapshot(&dir, &[stored]).unwrap();
+        assert!(!good.with_extension("json.tmp").exists());
+
+        // a newer snapshot that got mangled is skipped
+        let millis = now().timestamp_millis() + 1000;
+        fs::write(dir.join(format!("timers-{millis}.json")), "[{\"timer_id\":").unwrap();
+        let timers = newest_snapshot(&dir, &dead_letters).unwrap();
+        assert_eq!(timers.len(), 1);
+        assert_eq!(timers[0].level, 3);
+
+        // only the newest few are kept
+        for _ in 0..KEEP_SNAPSHOTS {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            write_snapshot(&dir, &[]).unwrap();
+        }
+        assert_eq!(snapshots(&dir).unwrap().len(), KEEP_SNAPSHOTS);
+        assert!(!good.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn old_duration_overrides_still_load() {
+        let json = r#"{
+            "timer_id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "created": "2025-03-01T19:00:00-05:00",
+            "structure_name": "Nightly NLHE",
+            "level": 3,
+            "clock_paused": true,
+            "clock_remaining": [600, 0],
+            "clock_asof": "2025-03-01T20:00:00-05:00",
+            "duration_override": [900, 0],
+            "subscriptions": {}
+        }"#;
+        let stored: StoredTournament = serde_json::from_str(json).unwrap();
+        let durations = &stored.durations;
+        assert_eq!(*durations, DurationOverrides::all_levels(Duration::minutes(15)));
+        let level = Level::Blinds {
+            game: "NLHE".to_string(),
+            betting_limit: None,
+            small: 1,
+            big: 2,
+            ante: None,
+            ante_kind: AnteKind::Everyone,
+            duration: Duration::minutes(20),
+        };
+        assert_eq!(durations.duration_for(3, &level), Some(Duration::minutes(15)));
+        // the old field is never written
+        let json = serde_json::to_string(&stored).unwrap();
+        assert!(!json.contains("duration_override"));
+        // and the old one minute warning before breaks is kept
+        assert_eq!(stored.warnings, Warnings::default());
+    }
+}
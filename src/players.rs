@@ -0,0 +1,351 @@
+//! The players registered in a tournament, who is still in it, and what they
+//! have paid to play
+
+use uuid::Uuid;
+
+use crate::model::*;
+
+/// Everyone that has registered for a tournament, in the order they registered
+#[derive(Clone, Default, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Players {
+    players: Vec<Player>,
+    /// every buy in, rebuy, etc. in the order they happened
+    #[serde(default)]
+    transactions: Vec<Transaction>,
+    /// who knocked out who, in the order it happened
+    #[serde(default)]
+    eliminations: Vec<Elimination>,
+}
+
+impl Players {
+    pub fn all(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn eliminations(&self) -> &[Elimination] {
+        &self.eliminations
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&Player> {
+        self.players.iter().find(|p| p.id == id)
+    }
+
+    /// How many entries there have been, counting each re-entry as another
+    /// entrant
+    pub fn entrants(&self) -> usize {
+        let re_entries = self
+            .transactions
+            .iter()
+            .filter(|t| t.kind == EntryKind::ReEntry)
+            .count();
+        self.players.len() + re_entries
+    }
+
+    /// The players that haven't busted out
+    pub fn remaining(&self) -> usize {
+        self.players.iter().filter(|p| p.busted.is_none()).count()
+    }
+
+    /// The average stack, counting the chips from every transaction. Players
+    /// that didn't pay a buy in are counted as starting with `starting_stack`.
+    pub fn average_stack(&self, starting_stack: Option<u32>) -> Option<u32> {
+        let remaining = self.remaining() as u64;
+        if remaining == 0 {
+            return None;
+        }
+        let without_buy_in = self
+            .players
+            .iter()
+            .filter(|p| !self.has_entry(p.id, EntryKind::BuyIn))
+            .count() as u64;
+        let mut chips: u64 = self.transactions.iter().map(|t| t.chips as u64).sum();
+        if without_buy_in > 0 {
+            chips += starting_stack? as u64 * without_buy_in;
+        }
+        Some((chips / remaining) as u32)
+    }
+
+    /// The players that have finished, with their finishing place (starting
+    /// at 1). Once there is only one player left, they've finished first.
+    pub fn finishers(&self) -> Vec<(usize, &Player)> {
+        let remaining = self.remaining();
+        let mut busted: Vec<&Player> = self.players.iter().filter(|p| p.busted.is_some()).collect();
+        // the last player out finished the highest
+        busted.sort_by_key(|p| std::cmp::Reverse(p.busted));
+        let mut finishers: Vec<(usize, &Player)> = busted
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| (remaining + i + 1, p))
+            .collect();
+        if remaining == 1
+            && let Some(winner) = self.players.iter().find(|p| p.busted.is_none())
+        {
+            finishers.insert(0, (1, winner));
+        }
+        finishers
+    }
+
+    fn has_entry(&self, id: Uuid, kind: EntryKind) -> bool {
+        self.transactions
+            .iter()
+            .any(|t| t.player == id && t.kind == kind)
+    }
+
+    /// Register a new player, paying the buy in if the tournament has one
+    pub fn enter(
+        &mut self,
+        name: &str,
+        seat: Option<u32>,
+        rules: &EntryRules,
+        level: usize,
+    ) -> Result<Uuid, String> {
+        if let Some(rule) = &rules.buy_in
+            && !rule.is_open(level)
+        {
+            return Err("Registration is closed".to_string());
+        }
+        let id = self.register(name, seat)?;
+        if rules.buy_in.is_some() {
+            self.add_entry(id, EntryKind::BuyIn, rules, level)?;
+        }
+        Ok(id)
+    }
+
+    /// Record a player paying for an entry, checking that it is allowed
+    pub fn add_entry(
+        &mut self,
+        id: Uuid,
+        kind: EntryKind,
+        rules: &EntryRules,
+        level: usize,
+    ) -> Result<(), String> {
+        let rule = rules
+            .get(kind)
+            .ok_or(format!("This tournament doesn't have a {}", kind.name()))?;
+        if !rule.is_open(level) {
+            return Err(format!("{} is closed", kind.name()));
+        }
+        let has_entry = |kind| self.has_entry(id, kind);
+        let player = self
+            .players
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or("Player not found".to_string())?;
+        let problem = match kind {
+            EntryKind::BuyIn if has_entry(EntryKind::BuyIn) => Some("has already bought in"),
+            EntryKind::AddOn if player.busted.is_some() => Some("has busted out"),
+            EntryKind::AddOn if has_entry(EntryKind::AddOn) => Some("has already added on"),
+            EntryKind::ReEntry if player.busted.is_none() => Some("hasn't busted out"),
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            return Err(format!("{} {problem}", player.name));
+        }
+        if matches!(kind, EntryKind::Rebuy | EntryKind::ReEntry) {
+            // they're back in
+            self.players
+                .iter_mut()
+                .filter(|p| p.id == id)
+                .for_each(|p| p.busted = None);
+        }
+        self.transactions.push(Transaction {
+            player: id,
+            kind,
+            price: rule.price,
+            chips: rule.chips,
+            level,
+            time: now(),
+        });
+        Ok(())
+    }
+
+    pub fn register(&mut self, name: &str, seat: Option<u32>) -> Result<Uuid, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("The player needs a name".to_string());
+        }
+        if self.players.iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+            return Err(format!("{name} is already registered"));
+        }
+        if let Some(seat) = seat
+            && let Some(p) = self
+                .players
+                .iter()
+                .find(|p| p.busted.is_none() && p.table.is_none() && p.seat == Some(seat))
+        {
+            return Err(format!("{} is already in seat {seat}", p.name));
+        }
+        let id = crate::events::new_id();
+        self.players.push(Player {
+            id,
+            name: name.to_string(),
+            seat,
+            table: None,
+            busted: None,
+        });
+        Ok(id)
+    }
+
+    /// remove a player entirely, e.g. when they were registered by mistake
+    pub fn unregister(&mut self, id: Uuid) -> Result<(), String> {
+        let before = self.players.len();
+        self.players.retain(|p| p.id != id);
+        if self.players.len() == before {
+            return Err("Player not found".to_string());
+        }
+        self.transactions.retain(|t| t.player != id);
+        self.eliminations.retain(|e| e.player != id && e.by != id);
+        Ok(())
+    }
+
+    /// Move a player to a seat at one of the tables
+    pub fn set_seat(&mut self, id: Uuid, seat: Seat) {
+        if let Some(p) = self.players.iter_mut().find(|p| p.id == id) {
+            p.table = Some(seat.table);
+            p.seat = Some(seat.seat);
+        }
+    }
+
+    pub fn bust(&mut self, id: Uuid) -> Result<(), String> {
+        let player = self
+            .players
+            .iter_mut()
+            .find(|p| p.id == id)
+            .ok_or("Player not found".to_string())?;
+        if player.busted.is_some() {
+            return Err(format!("{} has already busted out", player.name));
+        }
+        player.busted = Some(now());
+        Ok(())
+    }
+
+    /// Bust a player out, recording who knocked them out if anyone did
+    pub fn knock_out(&mut self, id: Uuid, by: Option<Uuid>) -> Result<(), String> {
+        if let Some(by) = by {
+            let winner = self.get(by).ok_or("Player not found".to_string())?;
+            if by == id {
+                return Err(format!("{} can't knock themselves out", winner.name));
+            }
+            if winner.busted.is_some() {
+                return Err(format!("{} has already busted out", winner.name));
+            }
+        }
+        self.bust(id)?;
+        if let Some(by) = by {
+            self.eliminations.push(Elimination {
+                player: id,
+                by,
+                time: now(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busting_out_changes_the_average_stack() {
+        let mut players = Players::default();
+        let alice = players.register("Alice", Some(1)).unwrap();
+        players.register("Bob", Some(2)).unwrap();
+        players.register("Carol", None).unwrap();
+        players.register("Dave", None).unwrap();
+        assert_eq!(players.average_stack(Some(1000)), Some(1000));
+        players.bust(alice).unwrap();
+        assert_eq!(players.entrants(), 4);
+        assert_eq!(players.remaining(), 3);
+        assert_eq!(players.average_stack(Some(1500)), Some(2000));
+        assert_eq!(players.average_stack(None), None);
+        assert!(players.bust(alice).is_err());
+    }
+
+    fn rules() -> EntryRules {
+        let rule = |price, chips, last_level| {
+            Some(EntryRule {
+                price,
+                chips,
+                last_level,
+            })
+        };
+        EntryRules {
+            buy_in: rule(20, 1000, Some(4)),
+            rebuy: rule(20, 1000, Some(4)),
+            add_on: rule(10, 2000, Some(5)),
+            re_entry: None,
+        }
+    }
+
+    #[test]
+    fn entries_follow_the_rules() {
+        let rules = rules();
+        let mut players = Players::default();
+        let alice = players.enter("Alice", None, &rules, 1).unwrap();
+        let bob = players.enter("Bob", None, &rules, 2).unwrap();
+        assert!(players.add_entry(alice, EntryKind::BuyIn, &rules, 2).is_err());
+        assert!(players.add_entry(alice, EntryKind::ReEntry, &rules, 2).is_err());
+
+        // a rebuy after busting puts you back in
+        players.bust(bob).unwrap();
+        players.add_entry(bob, EntryKind::Rebuy, &rules, 4).unwrap();
+        assert_eq!(players.remaining(), 2);
+        assert!(players.add_entry(bob, EntryKind::Rebuy, &rules, 5).is_err());
+
+        players.add_entry(alice, EntryKind::AddOn, &rules, 5).unwrap();
+        assert!(players.add_entry(alice, EntryKind::AddOn, &rules, 5).is_err());
+        assert!(players.enter("Carol", None, &rules, 5).is_err());
+
+        // 2 buy ins, a rebuy and an add on
+        assert_eq!(players.transactions().len(), 4);
+        assert_eq!(players.average_stack(None), Some(2500));
+
+        players.unregister(bob).unwrap();
+        assert_eq!(players.transactions().len(), 2);
+    }
+
+    #[test]
+    fn re_entries_count_as_entrants() {
+        let rules = EntryRules {
+            re_entry: Some(EntryRule {
+                price: 20,
+                chips: 1000,
+                last_level: None,
+            }),
+            ..rules()
+        };
+        let mut players = Players::default();
+        let alice = players.enter("Alice", None, &rules, 1).unwrap();
+        players.enter("Bob", None, &rules, 1).unwrap();
+        players.bust(alice).unwrap();
+        players.add_entry(alice, EntryKind::ReEntry, &rules, 2).unwrap();
+        players.bust(alice).unwrap();
+        players.add_entry(alice, EntryKind::ReEntry, &rules, 3).unwrap();
+        assert_eq!(players.entrants(), 4);
+        assert_eq!(players.remaining(), 2);
+        // a rebuy is the same entry
+        players.bust(alice).unwrap();
+        players.add_entry(alice, EntryKind::Rebuy, &rules, 3).unwrap();
+        assert_eq!(players.entrants(), 4);
+    }
+
+    #[test]
+    fn names_and_seats_must_be_free() {
+        let mut players = Players::default();
+        let alice = players.register("Alice", Some(1)).unwrap();
+        assert!(players.register("alice ", None).is_err());
+        assert!(players.register("Bob", Some(1)).is_err());
+        assert!(players.register("  ", None).is_err());
+        // a busted player's seat can be reused
+        players.bust(alice).unwrap();
+        players.register("Bob", Some(1)).unwrap();
+        players.unregister(alice).unwrap();
+        assert!(players.unregister(alice).is_err());
+        assert_eq!(players.entrants(), 1);
+    }
+}
@@ -0,0 +1,137 @@
+//! The results of a tournament, to download as a CSV file once it's over
+
+use crate::bounties::bounties;
+use crate::model::*;
+use crate::payouts::prize_pool;
+use crate::players::Players;
+
+/// How one player did
+#[derive(Clone, PartialEq, Debug)]
+pub struct ResultRow {
+    /// `None` while the player is still playing
+    pub place: Option<usize>,
+    pub name: String,
+    /// everything the player paid
+    pub paid: u32,
+    pub prize: u32,
+    /// bounties won by knocking players out, and the winner's own bounty
+    pub bounties: u32,
+    pub knockouts: usize,
+}
+
+/// A row for every player, the players still in first and then by place
+pub fn results(
+    players: &Players,
+    payouts: &PayoutConfig,
+    bounty: Option<&BountyConfig>,
+) -> Vec<ResultRow> {
+    let finishers = players.finishers();
+    let pool = prize_pool(players, payouts, bounty);
+    let bounties = bounty.map(|b| bounties(players, b)).unwrap_or_default();
+    let mut rows: Vec<ResultRow> = players
+        .all()
+        .iter()
+        .map(|p| {
+            let place = finishers
+                .iter()
+                .find(|(_, f)| f.id == p.id)
+                .map(|(place, _)| *place);
+            let prize = pool
+                .as_ref()
+                .and_then(|pool| pool.payouts.iter().find(|payout| Some(payout.place) == place))
+                .map(|payout| payout.amount)
+                .unwrap_or(0);
+            let bounty = bounties.iter().find(|b| b.player == p.id);
+            ResultRow {
+                place,
+                name: p.name.clone(),
+                paid: players
+                    .transactions()
+                    .iter()
+                    .filter(|t| t.player == p.id)
+                    .map(|t| t.price)
+                    .sum(),
+                prize,
+                // the winner keeps the bounty on their own head
+                bounties: bounty
+                    .map(|b| b.won + if place == Some(1) { b.bounty } else { 0 })
+                    .unwrap_or(0),
+                knockouts: bounty.map(|b| b.knockouts).unwrap_or(0),
+            }
+        })
+        .collect();
+    rows.sort_by_key(|r| r.place.unwrap_or(0));
+    rows
+}
+
+/// The results as a CSV file
+pub fn results_csv(rows: &[ResultRow]) -> String {
+    let mut csv = "Place,Name,Paid,Prize,Bounties,Total,Knockouts\n".to_string();
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            row.place.map(|p| p.to_string()).unwrap_or_default(),
+            csv_field(&row.name),
+            row.paid,
+            row.prize,
+            row.bounties,
+            row.prize + row.bounties,
+            row.knockouts,
+        ));
+    }
+    csv
+}
+
+/// quote a field if it has anything that would break the CSV
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_winner_gets_their_own_bounty() {
+        let rule = Some(EntryRule {
+            price: 50,
+            chips: 1000,
+            last_level: None,
+        });
+        let rules = EntryRules {
+            buy_in: rule,
+            ..Default::default()
+        };
+        let bounty = BountyConfig {
+            amount: 10,
+            kind: BountyKind::Progressive { cash_percent: 50 },
+        };
+        let mut players = Players::default();
+        let a = players.enter("Alice", None, &rules, 1).unwrap();
+        let b = players.enter("Bob, Jr.", None, &rules, 1).unwrap();
+        players.enter("Carol", None, &rules, 1).unwrap();
+        let rows = results(&players, &PayoutConfig::default(), Some(&bounty));
+        assert!(rows.iter().all(|r| r.place.is_none()));
+
+        players.knock_out(b, Some(a)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        players.knock_out(players.all()[2].id, Some(a)).unwrap();
+        let rows = results(&players, &PayoutConfig::default(), Some(&bounty));
+        assert_eq!(rows[0].name, "Alice");
+        // the 120 prize pool all goes to first with 3 players, plus 5 + 5 in
+        // bounties and the 20 on Alice's head
+        assert_eq!((rows[0].prize, rows[0].bounties, rows[0].knockouts), (120, 30, 2));
+        assert_eq!(rows[1].place, Some(2));
+        assert_eq!(
+            results_csv(&rows),
+            "Place,Name,Paid,Prize,Bounties,Total,Knockouts\n\
+             1,Alice,50,120,30,150,2\n\
+             2,Carol,50,0,0,0,0\n\
+             3,\"Bob, Jr.\",50,0,0,0,0\n"
+        );
+    }
+}
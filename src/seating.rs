@@ -0,0 +1,324 @@
+//! Seats the players at the tables and keeps the tables balanced as players
+//! bust out.
+//!
+//! Seats are drawn at random. After that, tables are broken as soon as the
+//! players left fit at one fewer table, and a player is moved whenever one
+//! table has two or more players more than another.
+
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+use uuid::Uuid;
+
+use crate::model::*;
+use crate::players::Players;
+
+/// Randomly seat everyone still playing at as few tables as they fit at,
+/// with the same number of players (give or take one) at each table
+pub fn draw_seats(players: &mut Players, table_size: u32, rng: &mut impl Rng) -> SeatingChange {
+    let table_size = table_size.max(2);
+    let mut playing: Vec<&Player> = players.all().iter().filter(|p| p.busted.is_none()).collect();
+    playing.shuffle(rng);
+    let tables = (playing.len() as u32).div_ceil(table_size).max(1);
+    // a random order of the seats at each table
+    let mut seats: Vec<Vec<u32>> = (0..tables)
+        .map(|_| {
+            let mut seats: Vec<u32> = (1..=table_size).collect();
+            seats.shuffle(rng);
+            seats
+        })
+        .collect();
+    let moves: Vec<(Uuid, TableMove)> = playing
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let table = i as u32 % tables;
+            let to = Seat {
+                table: table + 1,
+                seat: seats[table as usize].pop().unwrap(),
+            };
+            (
+                p.id,
+                TableMove {
+                    player: p.name.clone(),
+                    from: None,
+                    to,
+                },
+            )
+        })
+        .collect();
+    for (id, m) in &moves {
+        players.set_seat(*id, m.to);
+    }
+    SeatingChange {
+        broken_tables: vec![],
+        moves: moves.into_iter().map(|(_, m)| m).collect(),
+    }
+}
+
+/// Seat anyone that doesn't have a seat yet, break tables that aren't
+/// needed anymore and even out the tables. Does nothing until the seats have
+/// been drawn.
+pub fn balance(players: &mut Players, table_size: u32, rng: &mut impl Rng) -> SeatingChange {
+    let table_size = table_size.max(2);
+    let mut tables = Tables::new(players);
+    let mut change = SeatingChange::default();
+    if tables.tables.is_empty() {
+        return change;
+    }
+    let mut moves: Vec<(Uuid, TableMove)> = vec![];
+    let mut move_to = |tables: &mut Tables, id: Uuid, to_table: u32, rng: &mut _| {
+        let to = tables.take_seat(id, to_table, table_size, rng);
+        let player = players.get(id).unwrap();
+        match moves.iter_mut().find(|(moved, _)| *moved == id) {
+            // moving twice, the player only needs to know where they end up
+            Some((_, m)) => m.to = to,
+            None => moves.push((
+                id,
+                TableMove {
+                    player: player.name.clone(),
+                    from: player.table.zip(player.seat).map(|(table, seat)| Seat { table, seat }),
+                    to,
+                },
+            )),
+        }
+    };
+
+    // if the table size went down since the draw, the players in the seats
+    // that are gone need new ones
+    for seats in tables.tables.values_mut() {
+        tables.unseated.extend(seats.split_off(&(table_size + 1)).into_values());
+    }
+
+    // break the tables that the players left don't need
+    let playing = tables.playing() + tables.unseated.len();
+    while tables.tables.len() > 1 && playing <= (tables.tables.len() - 1) * table_size as usize {
+        let broken = tables.smallest(|_| true).unwrap();
+        let mut ids: Vec<Uuid> = tables.tables.remove(&broken).unwrap().into_values().collect();
+        ids.shuffle(rng);
+        change.broken_tables.push(broken);
+        for id in ids {
+            let to = tables.smallest(|seats| seats.len() < table_size as usize).unwrap();
+            move_to(&mut tables, id, to, rng);
+        }
+    }
+
+    // late registrations (and anyone who lost their seat) go to the shortest
+    // table, opening a new one if they're all full
+    for id in std::mem::take(&mut tables.unseated) {
+        let to = tables
+            .smallest(|seats| seats.len() < table_size as usize)
+            .unwrap_or_else(|| tables.tables.keys().max().unwrap() + 1);
+        move_to(&mut tables, id, to, rng);
+    }
+
+    // move players from the biggest table to the smallest until they're even
+    loop {
+        let from = tables.biggest().unwrap();
+        let to = tables.smallest(|_| true).unwrap();
+        if tables.tables[&from].len() <= tables.tables[&to].len() + 1 {
+            break;
+        }
+        let ids: Vec<Uuid> = tables.tables[&from].values().copied().collect();
+        let id = *ids.choose(rng).unwrap();
+        tables.tables.get_mut(&from).unwrap().retain(|_, p| *p != id);
+        move_to(&mut tables, id, to, rng);
+    }
+
+    for (id, m) in &moves {
+        players.set_seat(*id, m.to);
+    }
+    change.moves = moves.into_iter().map(|(_, m)| m).collect();
+    change
+}
+
+/// Who is sitting where, for the players still in
+struct Tables {
+    /// table number to seat number to player
+    tables: BTreeMap<u32, BTreeMap<u32, Uuid>>,
+    /// players still in without a seat at a table
+    unseated: Vec<Uuid>,
+}
+
+impl Tables {
+    fn new(players: &Players) -> Tables {
+        let mut tables: BTreeMap<u32, BTreeMap<u32, Uuid>> = BTreeMap::new();
+        let mut unseated = vec![];
+        for p in players.all().iter().filter(|p| p.busted.is_none()) {
+            match (p.table, p.seat) {
+                // a player back in after a rebuy may find someone else in
+                // their old seat
+                (Some(table), Some(seat))
+                    if !tables.get(&table).is_some_and(|t| t.contains_key(&seat)) =>
+                {
+                    tables.entry(table).or_default().insert(seat, p.id);
+                }
+                _ => unseated.push(p.id),
+            }
+        }
+        Tables { tables, unseated }
+    }
+
+    fn playing(&self) -> usize {
+        self.tables.values().map(|t| t.len()).sum()
+    }
+
+    /// the table with the fewest players that `ok` allows, taking the
+    /// highest numbered table on a tie
+    fn smallest(&self, ok: impl Fn(&BTreeMap<u32, Uuid>) -> bool) -> Option<u32> {
+        self.tables
+            .iter()
+            .filter(|(_, seats)| ok(seats))
+            .min_by_key(|(table, seats)| (seats.len(), std::cmp::Reverse(**table)))
+            .map(|(table, _)| *table)
+    }
+
+    /// the table with the most players, taking the lowest numbered table on
+    /// a tie
+    fn biggest(&self) -> Option<u32> {
+        self.tables
+            .iter()
+            .max_by_key(|(table, seats)| (seats.len(), std::cmp::Reverse(**table)))
+            .map(|(table, _)| *table)
+    }
+
+    /// sit the player in a random empty seat at the table, which has to have
+    /// fewer than `table_size` players, all in seats up to `table_size`
+    fn take_seat(&mut self, id: Uuid, table: u32, table_size: u32, rng: &mut impl Rng) -> Seat {
+        let seats = self.tables.entry(table).or_default();
+        let empty: Vec<u32> = (1..=table_size).filter(|s| !seats.contains_key(s)).collect();
+        let seat = *empty.choose(rng).unwrap();
+        seats.insert(seat, id);
+        Seat { table, seat }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn players(count: usize) -> Players {
+        let mut players = Players::default();
+        for i in 0..count {
+            players.register(&format!("Player {i}"), None).unwrap();
+        }
+        players
+    }
+
+    /// the number of players at each table
+    fn table_counts(players: &Players) -> Vec<usize> {
+        let mut counts: BTreeMap<u32, usize> = BTreeMap::new();
+        for p in players.all().iter().filter(|p| p.busted.is_none()) {
+            *counts.entry(p.table.unwrap()).or_default() += 1;
+        }
+        counts.into_values().collect()
+    }
+
+    fn no_shared_seats(players: &Players) {
+        let mut seats: Vec<_> = players
+            .all()
+            .iter()
+            .filter(|p| p.busted.is_none())
+            .map(|p| (p.table, p.seat))
+            .collect();
+        let count = seats.len();
+        seats.sort();
+        seats.dedup();
+        assert_eq!(seats.len(), count);
+    }
+
+    #[test]
+    fn the_draw_fills_the_tables_evenly() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut players = players(19);
+        let change = draw_seats(&mut players, 9, &mut rng);
+        assert_eq!(change.moves.len(), 19);
+        assert_eq!(table_counts(&players), vec![7, 6, 6]);
+        no_shared_seats(&players);
+        assert!(balance(&mut players, 9, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn busting_out_balances_and_breaks_tables() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut players = players(20);
+        draw_seats(&mut players, 8, &mut rng);
+        assert_eq!(table_counts(&players), vec![7, 7, 6]);
+
+        // two busts at the short table leaves it 3 short
+        let table_three: Vec<Uuid> = players
+            .all()
+            .iter()
+            .filter(|p| p.table == Some(3))
+            .map(|p| p.id)
+            .collect();
+        players.bust(table_three[0]).unwrap();
+        players.bust(table_three[1]).unwrap();
+        let change = balance(&mut players, 8, &mut rng);
+        assert_eq!(change.moves.len(), 2);
+        assert!(change.moves.iter().all(|m| m.to.table == 3));
+        assert_eq!(table_counts(&players), vec![6, 6, 6]);
+        no_shared_seats(&players);
+
+        // once everyone fits at two tables, one of them is broken
+        let busted: Vec<Uuid> = players
+            .all()
+            .iter()
+            .filter(|p| p.busted.is_none())
+            .take(2)
+            .map(|p| p.id)
+            .collect();
+        for id in busted {
+            players.bust(id).unwrap();
+        }
+        let change = balance(&mut players, 8, &mut rng);
+        assert_eq!(change.broken_tables.len(), 1);
+        assert_eq!(table_counts(&players), vec![8, 8]);
+        no_shared_seats(&players);
+    }
+
+    #[test]
+    fn smaller_tables_move_the_players_without_a_seat() {
+        let mut rng = StdRng::seed_from_u64(4);
+        // a short table keeps its players, as long as their seats are still there
+        let mut short = players(9);
+        draw_seats(&mut short, 9, &mut rng);
+        let gone: Vec<Uuid> = short
+            .all()
+            .iter()
+            .filter(|p| p.seat.unwrap() > 4)
+            .map(|p| p.id)
+            .collect();
+        for id in gone {
+            short.bust(id).unwrap();
+        }
+        assert!(balance(&mut short, 4, &mut rng).is_empty());
+
+        let mut players = players(18);
+        draw_seats(&mut players, 9, &mut rng);
+        assert_eq!(table_counts(&players), vec![9, 9]);
+        let change = balance(&mut players, 6, &mut rng);
+        assert_eq!(change.moves.len(), 6);
+        assert_eq!(table_counts(&players), vec![6, 6, 6]);
+        assert!(players.all().iter().all(|p| p.seat.unwrap() <= 6));
+        no_shared_seats(&players);
+        assert!(balance(&mut players, 6, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn late_registrations_get_a_seat() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut players = players(4);
+        assert!(balance(&mut players, 4, &mut rng).is_empty());
+        draw_seats(&mut players, 4, &mut rng);
+        players.register("Late", None).unwrap();
+        let change = balance(&mut players, 4, &mut rng);
+        // the new table gets a player from the full one
+        assert_eq!(change.moves.len(), 2);
+        assert_eq!(table_counts(&players), vec![3, 2]);
+        no_shared_seats(&players);
+    }
+}
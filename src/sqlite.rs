@@ -0,0 +1,357 @@
+//! Keeps the tournaments in an SQLite database instead of JSON files, so that
+//! they are still there after they end. Every tournament gets a row in
+//! `tournaments`, its players and how they did in `players`, and everything
+//! that happened to it in `events`, along with the level it was on, so you can
+//! see how long each level ran.
+//!
+//! The events of the tournaments that haven't ended are replayed when the
+//! server starts, the same as the JSON logs.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    events::{Log, LogEntry, TimerEvent},
+    model::*,
+    persistence::{DEAD_LETTERS, Storage, StoredTournament, read_records},
+    results::results,
+    timers::Tournament,
+};
+
+/// Where the database is kept
+pub const DATABASE: &str = "./storage/pokertimer.sqlite";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tournaments (
+        id INTEGER PRIMARY KEY,
+        timer_id TEXT NOT NULL,
+        structure_name TEXT NOT NULL,
+        created TEXT NOT NULL,
+        -- NULL while the tournament is running
+        ended TEXT,
+        -- the tournament as it is now, or as it ended, as JSON
+        state TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS running_tournaments ON tournaments (timer_id) WHERE ended IS NULL;
+    CREATE TABLE IF NOT EXISTS players (
+        tournament_id INTEGER NOT NULL REFERENCES tournaments (id),
+        name TEXT NOT NULL,
+        -- NULL while the player is still in
+        place INTEGER,
+        paid INTEGER NOT NULL,
+        prize INTEGER NOT NULL,
+        bounties INTEGER NOT NULL,
+        knockouts INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS tournament_players ON players (tournament_id);
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY,
+        tournament_id INTEGER NOT NULL REFERENCES tournaments (id),
+        at TEXT NOT NULL,
+        -- the level the tournament was on after the event
+        level INTEGER NOT NULL,
+        -- the log entry, as JSON
+        entry TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS tournament_events ON events (tournament_id);
+";
+
+pub struct SqliteStorage {
+    db: Mutex<Connection>,
+    /// where the rows that can't be read are quarantined
+    dead_letters: PathBuf,
+}
+
+impl SqliteStorage {
+    /// Open the database at `path`, making it if it isn't there
+    pub fn open(path: &Path) -> Result<SqliteStorage, Box<dyn std::error::Error>> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        SqliteStorage::new(Connection::open(path)?, Path::new(DEAD_LETTERS))
+    }
+
+    fn new(db: Connection, dead_letters: &Path) -> Result<SqliteStorage, Box<dyn std::error::Error>> {
+        // every event is written as it happens
+        db.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+        db.execute_batch(SCHEMA)?;
+        Ok(SqliteStorage {
+            db: Mutex::new(db),
+            dead_letters: dead_letters.to_path_buf(),
+        })
+    }
+}
+
+/// The running tournament of a timer
+fn running(tx: &Transaction, timer_id: Uuid) -> rusqlite::Result<Option<i64>> {
+    tx.query_row(
+        "SELECT id FROM tournaments WHERE timer_id = ?1 AND ended IS NULL ORDER BY id DESC",
+        params![timer_id.to_string()],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Bring a tournament's state and players up to date
+fn update(
+    tx: &Transaction,
+    tournament_id: i64,
+    stored: &StoredTournament,
+) -> Result<(), Box<dyn std::error::Error>> {
+    tx.execute(
+        "UPDATE tournaments SET state = ?1 WHERE id = ?2",
+        params![serde_json::to_string(stored)?, tournament_id],
+    )?;
+    tx.execute(
+        "DELETE FROM players WHERE tournament_id = ?1",
+        params![tournament_id],
+    )?;
+    for row in results(&stored.players, &stored.payouts, stored.bounty.as_ref()) {
+        tx.execute(
+            "INSERT INTO players (tournament_id, name, place, paid, prize, bounties, knockouts)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                tournament_id,
+                row.name,
+                row.place.map(|p| p as i64),
+                row.paid,
+                row.prize,
+                row.bounties,
+                row.knockouts as i64
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn save_running(&self, timers: &[StoredTournament]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        for stored in timers {
+            tx.execute(
+                "UPDATE tournaments SET state = ?1 WHERE timer_id = ?2 AND ended IS NULL",
+                params![serde_json::to_string(stored)?, stored.timer_id.to_string()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load_running(&self) -> Result<Option<Vec<StoredTournament>>, Box<dyn std::error::Error>> {
+        let db = self.db.lock().unwrap();
+        let mut query = db.prepare("SELECT state FROM tournaments WHERE ended IS NULL ORDER BY id")?;
+        let states = query
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let records = states
+            .into_iter()
+            .map(|state| serde_json::from_str(&state).unwrap_or(Value::String(state)))
+            .collect();
+        Ok(Some(read_records(records, DATABASE, &self.dead_letters)))
+    }
+
+    fn log_event(
+        &self,
+        timer_id: Uuid,
+        entry: &LogEntry,
+        tournament: &Tournament,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let stored = StoredTournament::from(tournament);
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        if let TimerEvent::Started(_) = entry.event {
+            // one that never ended is as over as it is going to get
+            tx.execute(
+                "UPDATE tournaments SET ended = ?1 WHERE timer_id = ?2 AND ended IS NULL",
+                params![entry.at.to_rfc3339(), timer_id.to_string()],
+            )?;
+            tx.execute(
+                "INSERT INTO tournaments (timer_id, structure_name, created, state)
+                 VALUES (?1, ?2, ?3, '')",
+                params![
+                    timer_id.to_string(),
+                    stored.structure_name,
+                    stored.created.to_rfc3339()
+                ],
+            )?;
+        }
+        let tournament_id =
+            running(&tx, timer_id)?.ok_or_else(|| format!("{timer_id} has no running tournament"))?;
+        tx.execute(
+            "INSERT INTO events (tournament_id, at, level, entry) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                tournament_id,
+                entry.at.to_rfc3339(),
+                stored.level as i64,
+                serde_json::to_string(entry)?
+            ],
+        )?;
+        update(&tx, tournament_id, &stored)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn end(&self, timer_id: Uuid, last: &StoredTournament) -> Result<(), Box<dyn std::error::Error>> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        if let Some(tournament_id) = running(&tx, timer_id)? {
+            update(&tx, tournament_id, last)?;
+            tx.execute(
+                "UPDATE tournaments SET ended = ?1 WHERE id = ?2",
+                params![now().to_rfc3339(), tournament_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn logs(&self) -> Result<Vec<Log>, Box<dyn std::error::Error>> {
+        let db = self.db.lock().unwrap();
+        let mut query = db.prepare(
+            "SELECT tournaments.timer_id, events.id, events.entry FROM events
+             JOIN tournaments ON tournaments.id = events.tournament_id
+             WHERE tournaments.ended IS NULL
+             ORDER BY tournaments.id, events.id",
+        )?;
+        let rows = query
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(query);
+        let mut logs: Vec<Log> = vec![];
+        for (timer_id, event_id, entry) in rows {
+            let timer_id: Uuid = timer_id.parse()?;
+            let record = serde_json::from_str(&entry).unwrap_or(Value::String(entry));
+            let Some(entry) = read_records(vec![record], DATABASE, &self.dead_letters).pop() else {
+                // it's in the dead letters now, and the rest of the log goes on without it
+                db.execute("DELETE FROM events WHERE id = ?1", params![event_id])?;
+                continue;
+            };
+            match logs.last_mut() {
+                Some((last, entries)) if *last == timer_id => entries.push(entry),
+                _ => logs.push((timer_id, vec![entry])),
+            }
+        }
+        Ok(logs)
+    }
+
+    fn last_ended(&self, timer_id: Uuid) -> Result<Option<StoredTournament>, Box<dyn std::error::Error>> {
+        let db = self.db.lock().unwrap();
+        let state: Option<String> = db
+            .query_row(
+                "SELECT state FROM tournaments WHERE timer_id = ?1 AND ended IS NOT NULL
+                 ORDER BY id DESC",
+                params![timer_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(state.map(|state| serde_json::from_str(&state)).transpose()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events::replay, timers::Timer};
+    use std::fs;
+
+    fn stored(timer_id: Uuid) -> StoredTournament {
+        serde_json::from_value(serde_json::json!({
+            "timer_id": timer_id,
+            "created": "2025-03-01T19:00:00-05:00",
+            "structure_name": "Nightly NLHE",
+            "level": 1,
+            "clock_paused": true,
+            "clock_remaining": [600, 0],
+            "clock_asof": "2025-03-01T19:00:00-05:00",
+            "subscriptions": {}
+        }))
+        .unwrap()
+    }
+
+    fn count(db: &SqliteStorage, sql: &str) -> i64 {
+        db.db.lock().unwrap().query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn tournaments_are_kept_after_they_end() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let dead_letters = std::env::temp_dir().join(format!("pokertimer-{}.jsonl", Uuid::new_v4()));
+            let db = SqliteStorage::new(Connection::open_in_memory().unwrap(), &dead_letters).unwrap();
+            let timer_id = Uuid::new_v4();
+            let mut timer = Timer::get_mut(timer_id);
+            let started = LogEntry::new(TimerEvent::Started(Box::new(stored(timer_id))));
+            replay(&mut timer, std::slice::from_ref(&started));
+            db.log_event(timer_id, &started, timer.tournament.as_ref().unwrap())
+                .unwrap();
+
+            let registered = LogEntry::new(TimerEvent::Command {
+                command: Command::RegisterPlayer {
+                    name: "Alice".to_string(),
+                    seat: None,
+                },
+                device_id: None,
+            });
+            replay(&mut timer, std::slice::from_ref(&registered));
+            db.log_event(timer_id, &registered, timer.tournament.as_ref().unwrap())
+                .unwrap();
+
+            // an entry that can't be read is quarantined, and the log goes on
+            // without it
+            db.db
+                .lock()
+                .unwrap()
+                .execute(
+                    "INSERT INTO events (tournament_id, at, level, entry) SELECT id, '', 1, '{' FROM tournaments",
+                    [],
+                )
+                .unwrap();
+            db.log_event(timer_id, &registered, timer.tournament.as_ref().unwrap())
+                .unwrap();
+            let logs = db.logs().unwrap();
+            assert_eq!(logs.len(), 1);
+            assert_eq!(logs[0].0, timer_id);
+            assert_eq!(logs[0].1.len(), 3);
+            assert_eq!(fs::read_to_string(&dead_letters).unwrap().lines().count(), 1);
+            assert_eq!(db.logs().unwrap()[0].1.len(), 3);
+            assert_eq!(fs::read_to_string(&dead_letters).unwrap().lines().count(), 1);
+            fs::remove_file(&dead_letters).unwrap();
+            assert_eq!(db.load_running().unwrap().unwrap().len(), 1);
+            assert_eq!(count(&db, "SELECT count(*) FROM players"), 1);
+
+            let last = StoredTournament::from(timer.tournament.as_ref().unwrap());
+            db.end(timer_id, &last).unwrap();
+            // nothing to replay, but it's all still there
+            assert!(db.logs().unwrap().is_empty());
+            assert!(db.load_running().unwrap().unwrap().is_empty());
+            assert_eq!(count(&db, "SELECT count(*) FROM events"), 3);
+            assert_eq!(count(&db, "SELECT count(*) FROM players WHERE place = 1"), 1);
+            assert_eq!(
+                count(&db, "SELECT count(*) FROM tournaments WHERE ended IS NOT NULL"),
+                1
+            );
+            // and so are the results
+            let ended = db.last_ended(timer_id).unwrap().unwrap();
+            assert_eq!(ended.players.entrants(), 1);
+            assert!(db.last_ended(Uuid::new_v4()).unwrap().is_none());
+
+            // the next tournament on the timer gets a row of its own
+            db.log_event(timer_id, &started, timer.tournament.as_ref().unwrap())
+                .unwrap();
+            assert_eq!(count(&db, "SELECT count(*) FROM tournaments"), 2);
+            assert_eq!(db.logs().unwrap()[0].1.len(), 1);
+        });
+    }
+}
@@ -1,25 +1,222 @@
-use std::{collections::HashMap, sync::Arc};
+//! The tournament structures available to the timers. A handful of structures
+//! are built in, and more can be dropped into `./storage/structures/` as JSON or
+//! TOML files without having to redeploy.
 
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use dashmap::DashMap;
 use once_cell::sync::Lazy;
 
 use crate::model::*;
 
-#[derive(Clone, PartialEq, serde::Deserialize, serde::Serialize)]
-pub struct Structure {
-    pub levels: Vec<Level>,
+/// Where structure files are loaded from
+pub const STRUCTURES_DIR: &str = "./storage/structures";
+
+/// All the structures that can be used to start a tournament, by name.
+/// Starts out with the built in structures, and is added to by `load_structures`
+pub static STRUCTURES: Lazy<DashMap<String, Arc<Structure>>> =
+    Lazy::new(|| builtin_structures().into_iter().collect());
+
+/// look up a structure by name
+pub fn get_structure(name: &str) -> Option<Arc<Structure>> {
+    STRUCTURES.get(name).map(|s| s.clone())
+}
+
+/// The names of all the available structures, in alphabetical order
+pub fn structure_names() -> Vec<String> {
+    let mut names: Vec<String> = STRUCTURES.iter().map(|s| s.key().clone()).collect();
+    names.sort();
+    names
+}
+
+/// The format of a structure file on disk. Durations are in minutes
+/// so that the files are easy to write by hand, e.g.
+///
+/// ```toml
+/// name = "Friday NLHE"
+///
+/// [[levels]]
+/// type = "blinds"
+/// game = "NLHE"
+/// small = 25
+/// big = 50
+/// minutes = 20
+///
+/// [[levels]]
+/// type = "break"
+/// minutes = 10
+/// ```
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct StructureFile {
+    /// defaults to the name of the file (without the extension)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub levels: Vec<LevelDef>,
+    /// the chip denominations, so color ups can say what the smallest chip is
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chips: Vec<u32>,
+}
+
+impl StructureFile {
+    /// Validate the file contents into a structure
+    pub fn to_structure(&self) -> Result<Structure, String> {
+        Structure::from_level_defs(&self.levels, &self.chips)
+    }
+}
+
+/// A structure file that could not be loaded
+#[derive(Debug)]
+pub struct StructureFileError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl fmt::Display for StructureFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.reason)
+    }
+}
+
+impl std::error::Error for StructureFileError {}
+
+/// Read a single structure file, returning its name and contents
+fn read_structure_file(path: &Path) -> Result<(String, Structure), String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let file: StructureFile = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => serde_json::from_str(&contents).map_err(|e| e.to_string())?,
+        Some("toml") => toml::from_str(&contents).map_err(|e| e.to_string())?,
+        _ => return Err("expected a .json or .toml file".to_string()),
+    };
+    let name = match &file.name {
+        Some(name) => name.trim().to_string(),
+        None => path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string(),
+    };
+    if name.is_empty() {
+        return Err("structure has no name".to_string());
+    }
+    Ok((name, file.to_structure()?))
+}
+
+/// Load every structure file in `dir` into the registry. A file with the same
+/// name as a built in structure replaces it. Files that can't be loaded are
+/// skipped and returned so the caller can report them.
+pub fn load_structures(dir: &Path) -> Vec<StructureFileError> {
+    let mut errors = vec![];
+    if !dir.exists() {
+        return errors;
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            errors.push(StructureFileError {
+                path: dir.to_path_buf(),
+                reason: e.to_string(),
+            });
+            return errors;
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    paths.sort();
+    for path in paths {
+        match read_structure_file(&path) {
+            Ok((name, structure)) => {
+                STRUCTURES.insert(name, Arc::new(structure));
+            }
+            Err(reason) => errors.push(StructureFileError { path, reason }),
+        }
+    }
+    errors
 }
 
-impl Structure {
-    pub fn get_level<'a>(&'a self, l: usize) -> &'a Level {
-        if l >= self.levels.len() {
-            &Level::Done
-        } else {
-            &self.levels[l - 1]
+/// Lint every available structure, returning each warning with the name of
+/// the structure it is for
+pub fn lint_structures() -> Vec<(String, StructureWarning)> {
+    let mut warnings = vec![];
+    for name in structure_names() {
+        if let Some(structure) = get_structure(&name) {
+            warnings.extend(structure.lint().into_iter().map(|w| (name.clone(), w)));
         }
     }
+    warnings
+}
+
+/// The structure's chips and its levels as a list of flattened levels, for editing
+pub fn structure_levels(name: &str) -> Option<(Vec<u32>, Vec<LevelDef>)> {
+    get_structure(name).map(|s| {
+        let levels = s.levels.iter().filter_map(LevelDef::from_level).collect();
+        (s.chips.clone(), levels)
+    })
+}
+
+/// Validate a structure from the editor, save it to the structures directory
+/// and make it available to new tournaments
+pub fn save_structure(name: &str, levels: Vec<LevelDef>, chips: Vec<u32>) -> Result<(), String> {
+    save_structure_in(Path::new(STRUCTURES_DIR), name, levels, chips)
+}
+
+fn save_structure_in(
+    dir: &Path,
+    name: &str,
+    levels: Vec<LevelDef>,
+    chips: Vec<u32>,
+) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("The structure needs a name".to_string());
+    }
+    let file = StructureFile {
+        name: Some(name.to_string()),
+        levels,
+        chips,
+    };
+    let structure = file.to_structure()?;
+
+    // keep the file name tame, the real name is stored inside the file
+    let file_name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let path = dir.join(format!("{file_name}.json"));
+    // names that only differ in the characters that were replaced end up in
+    // the same file, which mustn't lose the other structure
+    if let Ok((saved, _)) = read_structure_file(&path)
+        && saved != name
+    {
+        return Err(format!(
+            "The name is too close to the saved structure \"{saved}\", pick another one"
+        ));
+    }
+    let write = || -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(dir)?;
+        fs::write(&path, serde_json::to_vec_pretty(&file)?)?;
+        Ok(())
+    };
+    write().map_err(|e| format!("Couldn't save structure: {e}"))?;
+    STRUCTURES.insert(name.to_string(), Arc::new(structure));
+    Ok(())
 }
 
-pub static STRUCTURES: Lazy<HashMap<String, Arc<Structure>>> = Lazy::new(|| {
+/// The structures that ship with the timer
+fn builtin_structures() -> HashMap<String, Arc<Structure>> {
     let mut map = HashMap::new();
     map.insert(
         "HOSE".to_string(),
@@ -194,6 +391,7 @@ pub static STRUCTURES: Lazy<HashMap<String, Arc<Structure>>> = Lazy::new(|| {
                     duration: Duration::minutes(12),
                 },
             ],
+            chips: vec![],
         }),
     );
     map.insert(
@@ -242,106 +440,136 @@ pub static STRUCTURES: Lazy<HashMap<String, Arc<Structure>>> = Lazy::new(|| {
                 },
                 Level::Break {
                     duration: Duration::minutes(10),
+                    color_up: vec![],
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 500,
                     big: 1000,
                     ante: Some(1000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 600,
                     big: 1200,
                     ante: Some(1200),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1000,
                     big: 1500,
                     ante: Some(1500),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1000,
                     big: 2000,
                     ante: Some(2000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1500,
                     big: 2500,
                     ante: Some(2500),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1500,
                     big: 3000,
                     ante: Some(3000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 2000,
                     big: 4000,
                     ante: Some(4000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 2500,
                     big: 5000,
                     ante: Some(5000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 3000,
                     big: 6000,
                     ante: Some(6000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 4000,
                     big: 8000,
                     ante: Some(8000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 5000,
                     big: 10000,
                     ante: Some(10000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 6000,
                     big: 12000,
                     ante: Some(12000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 8000,
                     big: 16000,
                     ante: Some(16000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 10000,
                     big: 20000,
                     ante: Some(20000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
             ],
+            chips: vec![],
         }),
     );
 
@@ -351,155 +579,199 @@ pub static STRUCTURES: Lazy<HashMap<String, Arc<Structure>>> = Lazy::new(|| {
             levels: vec![
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 100,
                     big: 100,
                     ante: Some(100),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 100,
                     big: 200,
                     ante: Some(200),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 200,
                     big: 300,
                     ante: Some(300),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 200,
                     big: 400,
                     ante: Some(400),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 300,
                     big: 500,
                     ante: Some(500),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 300,
                     big: 600,
                     ante: Some(600),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Break {
                     duration: Duration::minutes(10),
+                    color_up: vec![],
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 400,
                     big: 800,
                     ante: Some(800),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 500,
                     big: 1000,
                     ante: Some(1000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 600,
                     big: 1200,
                     ante: Some(1200),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1000,
                     big: 1500,
                     ante: Some(1500),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1000,
                     big: 2000,
                     ante: Some(2000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1500,
                     big: 2500,
                     ante: Some(2500),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1500,
                     big: 3000,
                     ante: Some(3000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 2000,
                     big: 4000,
                     ante: Some(4000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 2500,
                     big: 5000,
                     ante: Some(5000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 3000,
                     big: 6000,
                     ante: Some(6000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 4000,
                     big: 8000,
                     ante: Some(8000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 5000,
                     big: 10000,
                     ante: Some(10000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 6000,
                     big: 12000,
                     ante: Some(12000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 10000,
                     big: 15000,
                     ante: Some(15000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 10000,
                     big: 20000,
                     ante: Some(20000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
             ],
+            chips: vec![],
         }),
     );
     map.insert(
@@ -508,148 +780,190 @@ pub static STRUCTURES: Lazy<HashMap<String, Arc<Structure>>> = Lazy::new(|| {
             levels: vec![
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 100,
                     big: 200,
                     ante: Some(200),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 200,
                     big: 300,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 200,
                     big: 400,
                     ante: Some(400),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 300,
                     big: 500,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 300,
                     big: 600,
                     ante: Some(600),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Break {
                     duration: Duration::minutes(10),
+                    color_up: vec![],
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 400,
                     big: 800,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 500,
                     big: 1000,
                     ante: Some(1000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 600,
                     big: 1200,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1000,
                     big: 1500,
                     ante: Some(1500),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 1000,
                     big: 2000,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 1500,
                     big: 2500,
                     ante: Some(2500),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 1500,
                     big: 3000,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 2000,
                     big: 4000,
                     ante: Some(4000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 2500,
                     big: 5000,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 3000,
                     big: 6000,
                     ante: Some(6000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 4000,
                     big: 8000,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 5000,
                     big: 10000,
                     ante: Some(10000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 6000,
                     big: 12000,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "NLHE".to_string(),
+                    betting_limit: None,
                     small: 10000,
                     big: 15000,
                     ante: Some(15000),
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
                 Level::Blinds {
                     game: "PLO".to_string(),
+                    betting_limit: None,
                     small: 10000,
                     big: 20000,
                     ante: None,
+                    ante_kind: AnteKind::Everyone,
                     duration: Duration::minutes(20),
                 },
             ],
+            chips: vec![],
         }),
     );
     map.insert(
@@ -688,6 +1002,7 @@ pub static STRUCTURES: Lazy<HashMap<String, Arc<Structure>>> = Lazy::new(|| {
                 },
                 Level::Break {
                     duration: Duration::minutes(20),
+                    color_up: vec![],
                 },
                 Level::Limit {
                     game: "Hold Em".to_string(),
@@ -744,8 +1059,417 @@ pub static STRUCTURES: Lazy<HashMap<String, Arc<Structure>>> = Lazy::new(|| {
                     duration: Duration::minutes(20),
                 },
             ],
+            chips: vec![],
         }),
     );
 
     map
-});
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn builtin() -> Vec<(String, Arc<Structure>)> {
+        let mut structures: Vec<_> = builtin_structures().into_iter().collect();
+        structures.sort_by(|a, b| a.0.cmp(&b.0));
+        structures
+    }
+
+    #[test]
+    fn every_level_is_reachable() {
+        for (name, structure) in builtin() {
+            let mut level = structure.first_level().unwrap();
+            let mut seen = vec![structure.get_level(level).clone()];
+            while let Some(next) = structure.offset(level, 1) {
+                assert_eq!(structure.next_level(level), structure.get_level(next));
+                level = next;
+                seen.push(structure.get_level(level).clone());
+            }
+            assert_eq!(seen, structure.levels, "{name}");
+            assert_eq!(structure.next_level(level), &Level::Done, "{name}");
+        }
+    }
+
+    #[test]
+    fn cant_go_before_the_first_level() {
+        for (name, structure) in builtin() {
+            let first = structure.first_level().unwrap();
+            assert_eq!(structure.offset(first, -1), None, "{name}");
+            assert_eq!(structure.level_index(0), None, "{name}");
+            assert_eq!(structure.level_index(structure.levels.len() + 1), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn changing_the_kind_of_level_clears_what_it_doesnt_have() {
+        let mut level = LevelDef {
+            game: Some("NLHE".to_string()),
+            small: Some(100),
+            big: Some(200),
+            ante: Some(200),
+            ante_kind: Some(AnteKind::BigBlind),
+            betting_limit: Some(BettingLimit::NoLimit),
+            minutes: 20,
+            ..LevelDef::new("blinds")
+        };
+        level.set_kind("limit");
+        assert_eq!(level.kind, "limit");
+        assert_eq!((level.ante, level.ante_kind, level.betting_limit), (None, None, None));
+        assert!(level.to_level().is_ok());
+
+        level.set_kind("rotation");
+        level.games = vec!["Razz".to_string(), "Stud".to_string()];
+        level.hands = Some(8);
+        level.bring_in = Some(50);
+        assert!(level.to_level().is_ok());
+        level.set_kind("stud");
+        assert!(level.games.is_empty() && level.hands.is_none());
+        assert_eq!(level.bring_in, Some(50));
+
+        level.set_kind("break");
+        level.color_up = vec![25];
+        assert!(level.to_level().is_ok());
+        assert_eq!((level.game.clone(), level.small, level.bring_in), (None, None, None));
+        level.set_kind("blinds");
+        assert!(level.color_up.is_empty());
+        assert_eq!(level.minutes, 20);
+    }
+
+    #[test]
+    fn saving_doesnt_overwrite_another_structure() {
+        let dir = std::env::temp_dir().join(format!("pokertimer-{}", uuid::Uuid::new_v4()));
+        let id = uuid::Uuid::new_v4();
+        let levels = vec![LevelDef {
+            game: Some("NLHE".to_string()),
+            small: Some(25),
+            big: Some(50),
+            minutes: 20,
+            ..LevelDef::new("blinds")
+        }];
+        let name = format!("Saved {id}");
+        save_structure_in(&dir, &name, levels.clone(), vec![]).unwrap();
+        // saving it again is fine
+        save_structure_in(&dir, &name, levels.clone(), vec![25]).unwrap();
+        let close = format!("Saved_{id}");
+        let e = save_structure_in(&dir, &close, levels, vec![]).unwrap_err();
+        assert!(e.contains(&name), "{e}");
+        assert!(get_structure(&close).is_none());
+        let (saved, structure) = read_structure_file(&dir.join(format!("Saved_{id}.json"))).unwrap();
+        assert_eq!((saved, structure.chips), (name, vec![25]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn levels_without_time_dont_load() {
+        let dir = std::env::temp_dir().join(format!("pokertimer-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, minutes) in [("zero.toml", 0), ("negative.toml", -5)] {
+            fs::write(
+                dir.join(file),
+                format!(
+                    "[[levels]]\ntype = \"blinds\"\ngame = \"NLHE\"\nsmall = 25\nbig = 50\nminutes = 20\n\n\
+                     [[levels]]\ntype = \"break\"\nminutes = {minutes}\n"
+                ),
+            )
+            .unwrap();
+        }
+        let errors = load_structures(&dir);
+        assert_eq!(errors.len(), 2);
+        for e in &errors {
+            assert!(e.reason.starts_with("level 2: break level has to be at least a minute"), "{e}");
+        }
+        assert!(get_structure("zero").is_none());
+        assert!(get_structure("negative").is_none());
+        fs::remove_dir_all(&dir).unwrap();
+
+        // or longer than a clock can count
+        let endless = LevelDef {
+            minutes: i64::MAX,
+            ..LevelDef::new("break")
+        };
+        assert!(endless.to_level().is_err());
+    }
+
+    fn blinds(game: &str, small: u32, big: u32, minutes: i64) -> Level {
+        Level::Blinds {
+            game: game.to_string(),
+            betting_limit: None,
+            small,
+            big,
+            ante: None,
+            ante_kind: AnteKind::Everyone,
+            duration: Duration::minutes(minutes),
+        }
+    }
+
+    fn lint(levels: Vec<Level>) -> Vec<String> {
+        let structure = Structure {
+            levels,
+            chips: vec![],
+        };
+        structure.lint().iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn structures_from_elsewhere_need_time_in_every_level() {
+        let structure = |levels| Structure {
+            levels,
+            chips: vec![],
+        };
+        assert!(structure(vec![blinds("NLHE", 100, 200, 20)]).check().is_ok());
+        assert_eq!(
+            structure(vec![blinds("NLHE", 100, 200, 20), blinds("NLHE", 200, 400, 0)]).check(),
+            Err("level 2 has to be at least a minute long".to_string())
+        );
+        let rest = Level::Break {
+            duration: Duration::seconds(30),
+            color_up: vec![],
+        };
+        assert!(structure(vec![blinds("NLHE", 100, 200, 20), rest]).check().is_err());
+        assert!(structure(vec![]).check().is_err());
+        assert!(structure(vec![Level::Done]).check().is_err());
+    }
+
+    #[test]
+    fn lint_finds_blinds_going_down() {
+        assert_eq!(
+            lint(vec![blinds("NLHE", 100, 200, 20), blinds("NLHE", 50, 100, 20)]),
+            vec!["level 2: NLHE blinds go down from 100 / 200 to 50 / 100"]
+        );
+        // other games in a mix are compared with themselves
+        assert!(
+            lint(vec![
+                blinds("NLHE", 100, 200, 20),
+                blinds("PLO", 50, 100, 20),
+                blinds("NLHE", 150, 300, 20),
+            ])
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn lint_finds_big_blinds_that_arent_a_multiple_of_the_small() {
+        assert_eq!(
+            lint(vec![blinds("NLHE", 100, 250, 20)]),
+            vec!["level 1: big blind 250 is not a multiple of the small blind 100"]
+        );
+        assert_eq!(
+            lint(vec![blinds("NLHE", 0, 100, 20)]),
+            vec!["level 1: big blind 100 is not a multiple of the small blind 0"]
+        );
+    }
+
+    #[test]
+    fn lint_finds_levels_without_time() {
+        assert_eq!(
+            lint(vec![blinds("NLHE", 100, 200, 20), blinds("NLHE", 200, 400, 0)]),
+            vec!["level 2: level has no time"]
+        );
+    }
+
+    #[test]
+    fn lint_finds_breaks_back_to_back() {
+        let rest = || Level::Break {
+            duration: Duration::minutes(10),
+            color_up: vec![],
+        };
+        assert_eq!(
+            lint(vec![blinds("NLHE", 100, 200, 20), rest(), rest(), blinds("NLHE", 200, 400, 20)]),
+            vec!["level 3: two breaks in a row"]
+        );
+    }
+
+    #[test]
+    fn lint_finds_bring_ins_bigger_than_the_small_bet() {
+        let stud = |bring_in| Level::Stud {
+            game: "Stud".to_string(),
+            ante: 25,
+            bring_in,
+            small: 100,
+            big: 200,
+            duration: Duration::minutes(20),
+        };
+        assert_eq!(
+            lint(vec![stud(150)]),
+            vec!["level 1: bring in 150 is more than the small bet 100"]
+        );
+        assert!(lint(vec![stud(100)]).is_empty());
+    }
+
+    #[test]
+    fn lint_finds_empty_structures() {
+        assert_eq!(lint(vec![]), vec!["structure has no levels"]);
+    }
+
+    #[test]
+    fn breaks_can_color_up() {
+        let file: StructureFile = toml::from_str(
+            r#"
+            chips = [25, 100, 500]
+
+            [[levels]]
+            type = "blinds"
+            game = "NLHE"
+            small = 25
+            big = 50
+            minutes = 20
+
+            [[levels]]
+            type = "break"
+            color_up = [25]
+            minutes = 10
+
+            [[levels]]
+            type = "blinds"
+            game = "NLHE"
+            small = 150
+            big = 300
+            minutes = 20
+            "#,
+        )
+        .unwrap();
+        let structure = file.to_structure().unwrap();
+        let color_up = structure.color_up(structure.level_index(2).unwrap()).unwrap();
+        assert_eq!(color_up.to_string(), "Color up the 25s, the smallest chip is now 100");
+        assert_eq!(structure.color_up(structure.level_index(3).unwrap()), None);
+        // 150 needs the 25s that just came off
+        let warnings: Vec<String> = structure.lint().iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec!["level 3: small blind 150 can't be made with the smallest chip 100"]
+        );
+        // and the color up survives a trip through the editor
+        let defs: Vec<LevelDef> = structure.levels.iter().filter_map(LevelDef::from_level).collect();
+        assert_eq!(Structure::from_level_defs(&defs, &structure.chips), Ok(structure));
+    }
+
+    #[test]
+    fn antes_can_be_paid_by_one_player() {
+        let file: StructureFile = toml::from_str(
+            r#"
+            [[levels]]
+            type = "blinds"
+            game = "NLHE"
+            small = 400
+            big = 800
+            ante = 800
+            ante_kind = "big_blind"
+            minutes = 20
+
+            [[levels]]
+            type = "blinds"
+            game = "Omaha"
+            betting_limit = "pot_limit"
+            small = 400
+            big = 800
+            ante = 100
+            ante_kind = "button"
+            minutes = 20
+            "#,
+        )
+        .unwrap();
+        let structure = file.to_structure().unwrap();
+        let strings: Vec<String> = structure
+            .levels
+            .iter()
+            .map(|l| l.short_level_string(None))
+            .collect();
+        assert_eq!(
+            strings,
+            vec!["NLHE 400 / 800 BBA 800", "PL Omaha 400 / 800 Button Ante 100"]
+        );
+        let defs: Vec<LevelDef> = structure.levels.iter().filter_map(LevelDef::from_level).collect();
+        assert_eq!(Structure::from_level_defs(&defs, &[]), Ok(structure));
+
+        // levels saved before there were ante kinds are antes for everyone
+        let json = r#"{"Blinds":{"game":"NLHE","small":25,"big":50,"ante":5,"duration":[1200,0]}}"#;
+        let level: Level = serde_json::from_str(json).unwrap();
+        assert_eq!(level.short_level_string(None), "NLHE 25 / 50 / 5");
+
+        let bad = LevelDef {
+            ante_kind: Some(AnteKind::BigBlind),
+            minutes: 20,
+            ..LevelDef::new("stud")
+        };
+        assert!(bad.to_level().is_err());
+    }
+
+    #[test]
+    fn rotations_change_game_when_the_dealer_says() {
+        let file: StructureFile = toml::from_str(
+            r#"
+            [[levels]]
+            type = "rotation"
+            game = "HORSE"
+            games = ["Hold Em", "Omaha 8", "Razz", "Stud", "Stud 8"]
+            small = 100
+            big = 200
+            ante = 25
+            bring_in = 50
+            minutes = 30
+
+            [[levels]]
+            type = "rotation"
+            game = "HO"
+            games = ["Hold Em", "Omaha 8"]
+            hands = 8
+            small = 150
+            big = 300
+            minutes = 30
+            "#,
+        )
+        .unwrap();
+        let structure = file.to_structure().unwrap();
+        let horse = &structure.levels[0];
+        assert_eq!(
+            horse.short_level_string(None),
+            "HORSE 100 / 200  Big Bet: 400  Ante: 25 Bring: 50"
+        );
+        assert_eq!(horse.rotation_game(0), Some("Hold Em"));
+        assert_eq!(horse.rotation_game(7), Some("Razz"));
+        // the rotation carries on into the next level
+        assert_eq!(structure.levels[1].rotation_game(7), Some("Omaha 8"));
+        assert!(matches!(
+            structure.levels[1],
+            Level::Rotation { rotate: Rotation::Hands(8), .. }
+        ));
+        assert_eq!(Level::Done.rotation_game(0), None);
+        let defs: Vec<LevelDef> = structure.levels.iter().filter_map(LevelDef::from_level).collect();
+        assert_eq!(Structure::from_level_defs(&defs, &[]), Ok(structure));
+
+        let no_games = LevelDef {
+            game: Some("HORSE".to_string()),
+            small: Some(100),
+            big: Some(200),
+            minutes: 20,
+            ..LevelDef::new("rotation")
+        };
+        assert!(no_games.to_level().is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn walking_stays_in_the_structure(
+            structure in prop::sample::select(builtin()),
+            steps in prop::collection::vec(-3isize..=3, 0..200),
+        ) {
+            let (_name, structure) = structure;
+            let mut level = structure.first_level().unwrap();
+            for step in steps {
+                let target = level.number() as isize + step;
+                match structure.offset(level, step) {
+                    Some(next) => {
+                        prop_assert_eq!(next.number() as isize, target);
+                        prop_assert_ne!(structure.get_level(next), &Level::Done);
+                        level = next;
+                    }
+                    None => {
+                        prop_assert!(target < 1 || target > structure.levels.len() as isize);
+                    }
+                }
+            }
+        }
+    }
+}
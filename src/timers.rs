@@ -10,17 +10,65 @@ use codee::string::JsonSerdeWasmCodec;
 use dashmap::DashMap;
 use leptos::prelude::*;
 use leptos::server_fn::error::ServerFnErrorErr;
-use log::{error, info};
+use log::{error, info, warn};
 use once_cell::sync::Lazy;
 use tokio::time::sleep;
 use uuid::Uuid;
 
 use crate::backend::{Notification, Subscription, send_notification};
-use crate::persistence::StoredTournament;
-use crate::structures::{STRUCTURES, Structure};
+use crate::events::{LogEntry, TimerEvent, during, replaying, with_rng};
+use crate::persistence::{StoredTournament, snapshot_soon, storage};
+use crate::payouts::prize_pool;
+use crate::players::Players;
+use crate::results::{ResultRow, results};
+use crate::seating::{balance, draw_seats};
+use crate::structures::get_structure;
 
 static TIMERS: Lazy<DashMap<Uuid, Timer>> = Lazy::new(|| DashMap::new());
 
+/// how long before a scheduled start to tell everyone to take their seats
+const START_WARNING_MINUTES: i64 = 5;
+
+/// How many timers there can be at once, unless `MAX_TIMERS` says otherwise
+const DEFAULT_MAX_TIMERS: u64 = 10_000;
+
+/// How long a timer that nobody is using is kept, unless
+/// `IDLE_TIMER_MINUTES` says otherwise
+const DEFAULT_IDLE_MINUTES: u64 = 60;
+
+/// How often to look for timers that nobody is using
+const EVICTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+static MAX_TIMERS: Lazy<usize> =
+    Lazy::new(|| env_number("MAX_TIMERS", DEFAULT_MAX_TIMERS) as usize);
+
+/// A positive number from an environment variable, or `default`
+fn env_number(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(default)
+}
+
+/// How long a timer that nobody is using is kept, from the
+/// `IDLE_TIMER_MINUTES` environment variable
+pub fn idle_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(env_number("IDLE_TIMER_MINUTES", DEFAULT_IDLE_MINUTES) * 60)
+}
+
+/// A new timer was asked for, but there are already `MAX_TIMERS` of them
+#[derive(Debug)]
+pub struct TooManyTimers;
+
+impl std::fmt::Display for TooManyTimers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "There are too many timers, try again later")
+    }
+}
+
+impl std::error::Error for TooManyTimers {}
+
 // an internal message that is passed on the backend message bus
 #[derive(Clone, serde::Deserialize, serde::Serialize, Debug)]
 pub enum TournamentMessage {
@@ -32,11 +80,42 @@ pub enum TournamentMessage {
     Ended,
     Pause,
     Resume,
-    LevelUp(RoundState),
+    LevelUp(Box<RoundState>),
     /// The tournament settings changed
     Settings,
-    /// One minute warning till end of break
-    OneMinuteWarning,
+    /// The level ends soon
+    Warning(LevelWarning),
+    /// A player registered, unregistered, busted out or paid for an entry
+    Players,
+    /// The level that just started is the last one for rebuys
+    LastRebuyLevel,
+    /// Players have to change seats
+    Seating(SeatingChange),
+    /// The time left in the level was changed
+    TimeChanged,
+    /// A few minutes until the scheduled start
+    StartingSoon,
+    /// The scheduled start time came and the clock started
+    Begin,
+    /// Hand for hand started or stopped, or a table finished its hand
+    HandForHand,
+    /// Every table finished its hand, deal the next one
+    NextHand(u32),
+    /// The dealer moved on to the next game of a rotation
+    NextGame,
+}
+
+impl TournamentMessage {
+    /// Whether the tournament is different after this message, so it needs
+    /// to be snapshotted. The rest only tell people about something.
+    fn changes_state(&self) -> bool {
+        !matches!(
+            self,
+            TournamentMessage::Warning(_)
+                | TournamentMessage::LastRebuyLevel
+                | TournamentMessage::StartingSoon
+        )
+    }
 }
 
 pub struct Timer {
@@ -47,10 +126,46 @@ pub struct Timer {
     pub event_sender: async_broadcast::Sender<(TournamentMessage, Option<Uuid>)>,
     /// The currently running tournament (if there is one)
     pub tournament: Option<Tournament>,
+    /// when the sweep for idle timers first found nobody using this one
+    idle_since: Option<std::time::Instant>,
 }
 
 impl Timer {
-    /// timers are light weight enough that they just exist until the next time the server bounces
+    /// A timer for a request, making it if it doesn't exist yet. Anyone can
+    /// ask for any timer, so no more than `MAX_TIMERS` are made.
+    pub fn open(
+        timer_id: Uuid,
+    ) -> Result<dashmap::mapref::one::Ref<'static, Uuid, Timer>, TooManyTimers> {
+        match TIMERS.get(&timer_id) {
+            Some(x) => Ok(x),
+            None => Ok(Timer::open_mut(timer_id)?.downgrade()),
+        }
+    }
+
+    /// A timer for a request to change, making it if it doesn't exist yet
+    pub fn open_mut(
+        timer_id: Uuid,
+    ) -> Result<dashmap::mapref::one::RefMut<'static, Uuid, Timer>, TooManyTimers> {
+        Timer::open_within(timer_id, *MAX_TIMERS)
+    }
+
+    fn open_within(
+        timer_id: Uuid,
+        max: usize,
+    ) -> Result<dashmap::mapref::one::RefMut<'static, Uuid, Timer>, TooManyTimers> {
+        if !TIMERS.contains_key(&timer_id) && TIMERS.len() >= max {
+            // nothing is lost by dropping a timer nobody is using
+            evict_idle(std::time::Duration::ZERO);
+            if TIMERS.len() >= max {
+                warn!("Not making timer {timer_id}, there are already {max}");
+                return Err(TooManyTimers);
+            }
+        }
+        Ok(Timer::get_mut(timer_id))
+    }
+
+    /// A timer, making it if it doesn't exist yet however many there are.
+    /// Requests use `open`, this is for timers that the server knows about.
     pub fn get(timer_id: Uuid) -> dashmap::mapref::one::Ref<'static, Uuid, Timer> {
         match TIMERS.get(&timer_id) {
             // the happy path, where no write lock is needed
@@ -63,7 +178,14 @@ impl Timer {
             }
         }
     }
-    /// timers are light weight enough that they just exist until the next time the server bounces
+    /// A timer that already exists, for requests that only look at it, so
+    /// that asking about a timer doesn't make one
+    pub fn find(timer_id: Uuid) -> Option<dashmap::mapref::one::Ref<'static, Uuid, Timer>> {
+        TIMERS.get(&timer_id)
+    }
+
+    /// A timer to change, making it if it doesn't exist yet however many
+    /// there are
     pub fn get_mut(timer_id: Uuid) -> dashmap::mapref::one::RefMut<'static, Uuid, Timer> {
         let ret = TIMERS
             .entry(timer_id)
@@ -95,6 +217,7 @@ impl Timer {
             timer_id: timer_id.clone(),
             event_sender: tx,
             tournament: None,
+            idle_since: None,
         };
         // start a thread to do the broadcasting
         tokio::spawn(async move {
@@ -106,12 +229,17 @@ impl Timer {
                             info!("Overflow on channel: {e}")
                         }
                         async_broadcast::RecvError::Closed => {
-                            panic!("Timer channel is closed! Should stay open");
+                            // the timer was evicted
+                            break;
                         }
                     },
 
                     Ok((message, from_device_id)) => {
-                        if let Some(ref tournament) = Timer::get(timer_id).tournament {
+                        // looked up without making it, in case it was evicted
+                        let Some(timer) = TIMERS.get(&timer_id) else {
+                            continue;
+                        };
+                        if let Some(ref tournament) = timer.tournament {
                             let title = "Poker Timer Update";
                             let notification = match &message {
                                 TournamentMessage::Started => Notification {
@@ -131,7 +259,10 @@ impl Timer {
                                     let level = round_state.cur.short_level_string(None);
                                     Notification {
                                         title: &title,
-                                        body: &format!("Level Up: {level}"),
+                                        body: &match &round_state.color_up {
+                                            Some(color_up) => format!("Level Up: {level}. {color_up}"),
+                                            None => format!("Level Up: {level}"),
+                                        },
                                     }
                                 }
                                 TournamentMessage::Settings => Notification {
@@ -143,16 +274,55 @@ impl Timer {
                                     title: &title,
                                     body: "Tournament has been terminated",
                                 },
-                                TournamentMessage::OneMinuteWarning => Notification {
-                                    title: &title,
-                                    body: "Take your seats! One minute till CIA",
+                                TournamentMessage::Warning(warning) => {
+                                    if !warning.push {
+                                        continue;
+                                    }
+                                    Notification {
+                                        title,
+                                        body: &warning.message,
+                                    }
+                                }
+                                // players coming and going doesn't need a notification
+                                TournamentMessage::Players => continue,
+                                TournamentMessage::LastRebuyLevel => Notification {
+                                    title,
+                                    body: "Last level for rebuys",
+                                },
+                                TournamentMessage::StartingSoon => Notification {
+                                    title,
+                                    body: &format!(
+                                        "Take your seats! Starting in {START_WARNING_MINUTES} minutes"
+                                    ),
+                                },
+                                TournamentMessage::Begin => Notification {
+                                    title,
+                                    body: "Shuffle up and deal!",
+                                },
+                                // only the timer screens need to know about each table
+                                TournamentMessage::HandForHand => continue,
+                                // this happens every orbit or so, too often to notify
+                                TournamentMessage::NextGame => continue,
+                                TournamentMessage::NextHand(hand) => Notification {
+                                    title,
+                                    body: &format!("All tables done, deal hand {hand}"),
+                                },
+                                // the screens show it, and it comes with every press of +/-
+                                TournamentMessage::TimeChanged => continue,
+                                TournamentMessage::Seating(change) => Notification {
+                                    title,
+                                    body: &match change.broken_tables.as_slice() {
+                                        [] => format!("{} players need to move", change.moves.len()),
+                                        tables => format!(
+                                            "Break table {}",
+                                            tables.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ")
+                                        ),
+                                    },
                                 },
                                 TournamentMessage::NotificationChange(device_id) => {
                                     // this doesnt result in a notification except for the device that is
                                     // turning on the notification
-                                    if let Some(subscription) =
-                                        Timer::get(timer_id).subscription(device_id)
-                                    {
+                                    if let Some(subscription) = timer.subscription(device_id) {
                                         send_notification(
                                             subscription,
                                             &Notification {
@@ -182,6 +352,12 @@ impl Timer {
         new_timer
     }
 
+    /// Nothing is going on: no tournament, and no screens showing the timer.
+    /// The broadcast task has the only receiver.
+    fn is_idle(&self) -> bool {
+        self.tournament.is_none() && self.event_sender.receiver_count() <= 1
+    }
+
     pub fn subscription(&self, device_id: &Uuid) -> Option<&Subscription> {
         match &self.tournament {
             Some(tournament) => tournament.subscriptions.get(device_id),
@@ -189,9 +365,15 @@ impl Timer {
         }
     }
 
-    pub fn make_tournament(&mut self, structure_name: String) -> Result<(), ServerFnError> {
+    pub fn make_tournament(
+        &mut self,
+        structure_name: String,
+        structure: Option<Structure>,
+        starts_at: Option<DateTime>,
+    ) -> Result<(), ServerFnError> {
         if self.tournament.is_none() {
-            let tournament = Tournament::new(self, structure_name)?;
+            let tournament = Tournament::new(self, structure_name, structure, starts_at)?;
+            self.log_started(&tournament);
             self.tournament = Some(tournament);
             (&*self).broadcast(None, TournamentMessage::Started);
         }
@@ -203,12 +385,91 @@ impl Timer {
         storage: StoredTournament,
     ) -> Result<(), ServerFnErrorErr> {
         let tournament = Tournament::from_storage(self, storage)?;
+        if !replaying() {
+            self.log_started(&tournament);
+        }
         self.tournament = Some(tournament);
         (&*self).broadcast(None, TournamentMessage::Started);
         Ok(())
     }
 
+    /// Start the log of the tournament with everything needed to rebuild it
+    fn log_started(&self, tournament: &Tournament) {
+        let entry = LogEntry::new(TimerEvent::Started(Box::new(tournament.into())));
+        if let Err(e) = storage().log_event(self.timer_id, &entry, tournament) {
+            error!("Couldn't log the start of {}: {e}", self.timer_id);
+        }
+    }
+
+    /// Make something happen to the tournament and log it, so it can be
+    /// replayed after a restart. Once the tournament ends, the log is
+    /// compacted.
+    fn record(&mut self, event: TimerEvent) -> Result<(), String> {
+        let last = match &self.tournament {
+            Some(tournament) if event.can_end() => Some(StoredTournament::from(tournament)),
+            _ => None,
+        };
+        let entry = LogEntry::new(event);
+        self.apply(&entry)?;
+        if let Some(tournament) = &self.tournament
+            && let Err(e) = storage().log_event(self.timer_id, &entry, tournament)
+        {
+            error!("Couldn't log an event for {}: {e}", self.timer_id);
+        }
+        if let Some(last) = last
+            && self.tournament.is_none()
+            && let Err(e) = storage().end(self.timer_id, &last)
+        {
+            error!("Couldn't store the end of {}: {e}", self.timer_id);
+        }
+        Ok(())
+    }
+
+    /// Make a logged event happen, at the time it was logged
+    pub fn apply(&mut self, entry: &LogEntry) -> Result<(), String> {
+        during(entry, || match &entry.event {
+            TimerEvent::Started(stored) => self
+                .make_tournament_from_storage((**stored).clone())
+                .map_err(|e| e.to_string()),
+            TimerEvent::Command { command, device_id } => self.run_command(command, *device_id),
+            TimerEvent::Settings(settings) => {
+                self.change_settings(settings.clone());
+                Ok(())
+            }
+            TimerEvent::Subscribed {
+                device_id,
+                subscription,
+            } => {
+                self.add_subscription(*device_id, subscription.clone());
+                Ok(())
+            }
+            TimerEvent::Unsubscribed(device_id) => {
+                self.remove_subscription(*device_id);
+                Ok(())
+            }
+            TimerEvent::LevelUp => {
+                self.level_up(1);
+                Ok(())
+            }
+            TimerEvent::Begin => {
+                self.begin();
+                Ok(())
+            }
+            TimerEvent::Ended(_) => {
+                self.tournament = None;
+                Ok(())
+            }
+        })
+    }
+
     fn broadcast(&self, from_device_id: Option<Uuid>, message: TournamentMessage) {
+        if replaying() {
+            // everyone was told the first time
+            return;
+        }
+        if message.changes_state() {
+            snapshot_soon();
+        }
         let result = self
             .event_sender
             .try_broadcast((message.clone(), from_device_id));
@@ -221,6 +482,15 @@ impl Timer {
     }
 
     pub fn subscribe(&mut self, device_id: Uuid, payload: Subscription) {
+        if self.tournament.is_some() {
+            let _ = self.record(TimerEvent::Subscribed {
+                device_id,
+                subscription: payload,
+            });
+        }
+    }
+
+    fn add_subscription(&mut self, device_id: Uuid, payload: Subscription) {
         match &mut self.tournament {
             Some(tournament) => {
                 tournament.subscriptions.remove(&device_id);
@@ -233,6 +503,12 @@ impl Timer {
     }
 
     pub fn unsubscribe(&mut self, device_id: Uuid) {
+        if self.tournament.is_some() {
+            let _ = self.record(TimerEvent::Unsubscribed(device_id));
+        }
+    }
+
+    fn remove_subscription(&mut self, device_id: Uuid) {
         match &mut self.tournament {
             Some(tournament) => {
                 tournament.subscriptions.remove(&device_id);
@@ -259,9 +535,13 @@ impl Timer {
                 true
             }
             LevelUpResult::Ok => {
-                let message =
-                    TournamentMessage::LevelUp(self.tournament.as_ref().unwrap().to_roundstate());
-                (&*self).broadcast(None, message);
+                let round_state = self.tournament.as_ref().unwrap().to_roundstate();
+                let last_rebuy_level = round_state.last_rebuy_level;
+                (&*self).broadcast(None, TournamentMessage::LevelUp(Box::new(round_state)));
+                // going back to it isn't news
+                if last_rebuy_level && delta > 0 {
+                    self.broadcast(None, TournamentMessage::LastRebuyLevel);
+                }
                 false
             }
         }
@@ -277,21 +557,31 @@ impl Timer {
             let subscribed = device.is_some_and(|id| tournament.subscriptions.contains_key(&id));
             TimerCompState::Running {
                 subscribed,
-                state: tournament.to_roundstate(),
+                state: Box::new(tournament.to_roundstate()),
             }
         } else {
             TimerCompState::NoTournament
         }
     }
-    pub fn update_settings(&mut self, duration_override: Option<Duration>) {
+    pub fn update_settings(&mut self, settings: TournamentConfig) {
+        if self.tournament.is_some() {
+            let _ = self.record(TimerEvent::Settings(settings));
+        }
+    }
+
+    fn change_settings(&mut self, settings: TournamentConfig) {
         if let Some(tournament) = &mut self.tournament {
-            tournament.update_settings(duration_override);
+            tournament.update_settings(settings);
             (&*self).broadcast(None, TournamentMessage::Settings);
         }
     }
 
     fn resume_tournament(&mut self, device_id: Option<Uuid>) {
-        if let Some(tournament) = &mut self.tournament {
+        if let Some(tournament) = &mut self.tournament
+            && tournament.hand_for_hand.is_none()
+        {
+            // starting by hand before the scheduled time
+            tournament.starts_at = None;
             tournament.clock_state = tournament.clock_state.resume();
             (&*self).broadcast(device_id, TournamentMessage::Resume);
         }
@@ -303,7 +593,70 @@ impl Timer {
             (&*self).broadcast(device_id, TournamentMessage::Pause);
         }
     }
-    pub fn execute(&mut self, cmd: &Command, device_id: Option<Uuid>) {
+    /// Start the clock at the scheduled start time
+    fn begin(&mut self) {
+        if let Some(tournament) = &mut self.tournament {
+            tournament.starts_at = None;
+            tournament.clock_state = tournament.clock_state.resume();
+            self.broadcast(None, TournamentMessage::Begin);
+        }
+    }
+
+    /// Change the time left in the current level without changing levels
+    fn change_time(
+        &mut self,
+        device_id: Option<Uuid>,
+        f: impl FnOnce(&ClockState) -> Result<ClockState, String>,
+    ) -> Result<(), String> {
+        match &mut self.tournament {
+            Some(tournament) => {
+                tournament.clock_state = f(&tournament.clock_state)?;
+                self.broadcast(device_id, TournamentMessage::TimeChanged);
+                Ok(())
+            }
+            None => Err("No tournament running".to_string()),
+        }
+    }
+
+    /// Update the players, letting everyone know if something changed. `f` is
+    /// also given the entry rules and the current level number
+    fn update_players(
+        &mut self,
+        f: impl FnOnce(&mut Players, &EntryRules, usize) -> Result<(), String>,
+    ) -> Result<(), String> {
+        match &mut self.tournament {
+            Some(tournament) => {
+                f(
+                    &mut tournament.players,
+                    &tournament.entries,
+                    tournament.level.number(),
+                )?;
+                let change = match tournament.table_size {
+                    Some(size) => with_rng(|rng| balance(&mut tournament.players, size, rng)),
+                    None => SeatingChange::default(),
+                };
+                let next_hand = tournament.update_hand_for_hand_tables();
+                self.broadcast(None, TournamentMessage::Players);
+                if !change.is_empty() {
+                    self.broadcast(None, TournamentMessage::Seating(change));
+                }
+                if let Some(hand) = next_hand {
+                    self.broadcast(None, TournamentMessage::NextHand(hand));
+                }
+                Ok(())
+            }
+            None => Err("No tournament running".to_string()),
+        }
+    }
+
+    pub fn execute(&mut self, cmd: &Command, device_id: Option<Uuid>) -> Result<(), String> {
+        self.record(TimerEvent::Command {
+            command: cmd.clone(),
+            device_id,
+        })
+    }
+
+    fn run_command(&mut self, cmd: &Command, device_id: Option<Uuid>) -> Result<(), String> {
         match cmd {
             Command::Resume => {
                 self.resume_tournament(device_id);
@@ -320,7 +673,97 @@ impl Timer {
             Command::Terminate => {
                 self.terminate();
             }
+            Command::RegisterPlayer { name, seat } => {
+                return self.update_players(|p, rules, level| {
+                    p.enter(name, *seat, rules, level).map(|_| ())
+                });
+            }
+            Command::UnregisterPlayer(id) => {
+                return self.update_players(|p, _, _| p.unregister(*id));
+            }
+            Command::BustPlayer { player, by } => {
+                return self.update_players(|p, _, _| p.knock_out(*player, *by));
+            }
+            Command::AddEntry { player, kind } => {
+                return self.update_players(|p, rules, level| {
+                    p.add_entry(*player, *kind, rules, level)
+                });
+            }
+            Command::DrawSeats => {
+                let Some(tournament) = &mut self.tournament else {
+                    return Err("No tournament running".to_string());
+                };
+                let size = tournament
+                    .table_size
+                    .ok_or("Set the table size on the settings page first".to_string())?;
+                let change = with_rng(|rng| draw_seats(&mut tournament.players, size, rng));
+                let next_hand = tournament.update_hand_for_hand_tables();
+                self.broadcast(None, TournamentMessage::Players);
+                self.broadcast(None, TournamentMessage::Seating(change));
+                if let Some(hand) = next_hand {
+                    self.broadcast(None, TournamentMessage::NextHand(hand));
+                }
+            }
+            Command::SetTime(remaining) => {
+                return self.change_time(device_id, |c| Ok(c.set_remaining(*remaining)));
+            }
+            Command::AddTime(delta) => {
+                return self.change_time(device_id, |c| {
+                    let remaining = c.remaining().checked_add(delta).ok_or("That's too much time")?;
+                    Ok(c.set_remaining(remaining))
+                });
+            }
+            Command::StartHandForHand => {
+                let Some(tournament) = &mut self.tournament else {
+                    return Err("No tournament running".to_string());
+                };
+                if tournament.hand_for_hand.is_none() {
+                    tournament.hand_for_hand = Some(HandForHand {
+                        clock_was_paused: tournament.clock_state.is_paused(),
+                        ..HandForHand::new(tournament.tables())
+                    });
+                    tournament.clock_state = tournament.clock_state.pause();
+                    self.broadcast(device_id, TournamentMessage::HandForHand);
+                }
+            }
+            Command::StopHandForHand => {
+                let Some(tournament) = &mut self.tournament else {
+                    return Err("No tournament running".to_string());
+                };
+                if let Some(hand_for_hand) = tournament.hand_for_hand.take() {
+                    if !hand_for_hand.clock_was_paused {
+                        tournament.clock_state = tournament.clock_state.resume();
+                    }
+                    self.broadcast(device_id, TournamentMessage::HandForHand);
+                }
+            }
+            Command::HandComplete(table) => {
+                let Some(hand_for_hand) = self
+                    .tournament
+                    .as_mut()
+                    .and_then(|t| t.hand_for_hand.as_mut())
+                else {
+                    return Err("Not playing hand for hand".to_string());
+                };
+                if hand_for_hand.hand_complete(*table)? {
+                    let hand = hand_for_hand.hand;
+                    self.broadcast(None, TournamentMessage::NextHand(hand));
+                } else {
+                    self.broadcast(None, TournamentMessage::HandForHand);
+                }
+            }
+            Command::NextGame => {
+                let Some(tournament) = &mut self.tournament else {
+                    return Err("No tournament running".to_string());
+                };
+                if tournament.structure.get_level(tournament.level).rotation_game(0).is_none() {
+                    return Err("This level doesn't rotate games".to_string());
+                }
+                tournament.rotation += 1;
+                self.broadcast(device_id, TournamentMessage::NextGame);
+            }
         }
+        Ok(())
     }
 }
 
@@ -328,10 +771,28 @@ pub struct Tournament {
     pub created: DateTime,
     pub timer_id: Uuid,
     pub structure_name: String,
-    structure: Arc<Structure>,
-    pub level: usize,
+    pub structure: Arc<Structure>,
+    pub level: LevelIndex,
     pub clock_state: ClockState,
-    pub duration_override: Option<Duration>,
+    /// level lengths that are different from the structure
+    pub durations: DurationOverrides,
+    pub starting_stack: Option<u32>,
+    /// the buy ins, rebuys, etc. that are allowed
+    pub entries: EntryRules,
+    pub payouts: PayoutConfig,
+    pub players: Players,
+    /// the most players at a table, when seats are drawn
+    pub table_size: Option<u32>,
+    pub bounty: Option<BountyConfig>,
+    /// the warnings given before levels end
+    pub warnings: Warnings,
+    /// when the clock starts by itself, if it hasn't started yet
+    pub starts_at: Option<DateTime>,
+    /// the clock is stopped while playing hand for hand
+    pub hand_for_hand: Option<HandForHand>,
+    /// the number of times the dealer has moved on to the next game in
+    /// rotation levels. Carries on from one level to the next
+    pub rotation: usize,
     /// The devices that have PWA notification active for the current tournament
     pub subscriptions: HashMap<Uuid, Subscription>,
 }
@@ -346,10 +807,17 @@ impl Tournament {
     fn from_storage(timer: &Timer, args: StoredTournament) -> Result<Tournament, ServerFnError> {
         let rx = timer.event_sender.new_receiver();
         let timer_id = timer.timer_id;
-        let structure = STRUCTURES
-            .get(&args.structure_name)
-            .ok_or(ServerFnError::new("Structure not found"))?
-            .clone();
+        let durations = args.durations;
+        // tournaments saved before the structure was stored with them have to
+        // look it up by name
+        let structure = match args.structure {
+            Some(structure) => Arc::new(structure),
+            None => get_structure(&args.structure_name)
+                .ok_or(ServerFnError::new("Structure not found"))?,
+        };
+        let level = structure
+            .level_index(args.level)
+            .ok_or(ServerFnError::new("Level is not in the structure"))?;
         let clock = if args.clock_paused {
             ClockState::Paused {
                 remaining: args.clock_remaining,
@@ -365,33 +833,68 @@ impl Tournament {
             timer_id: timer.timer_id,
             structure_name: args.structure_name,
             structure: structure.clone(),
-            level: args.level,
+            level,
             clock_state: clock,
-            duration_override: args.duration_override,
+            durations,
+            starting_stack: args.starting_stack,
+            entries: args.entries,
+            payouts: args.payouts,
+            players: args.players,
+            table_size: args.table_size,
+            bounty: args.bounty,
+            warnings: args.warnings,
+            starts_at: args.starts_at,
+            hand_for_hand: args.hand_for_hand,
+            rotation: args.rotation,
             subscriptions: args.subscriptions,
         };
         tournament.init(timer_id, rx);
         return Ok(tournament);
     }
 
-    fn new(timer: &Timer, structure_name: String) -> Result<Tournament, ServerFnError> {
+    /// Start a tournament with the named structure, or with a one off structure
+    /// if one is given
+    fn new(
+        timer: &Timer,
+        structure_name: String,
+        structure: Option<Structure>,
+        starts_at: Option<DateTime>,
+    ) -> Result<Tournament, ServerFnError> {
         let rx = timer.event_sender.new_receiver();
         let timer_id = timer.timer_id;
-        let structure = STRUCTURES
-            .get(&structure_name)
-            .ok_or(ServerFnError::new("Structure not found"))?
-            .clone();
+        let structure = match structure {
+            Some(structure) => {
+                structure.check().map_err(ServerFnError::new)?;
+                Arc::new(structure)
+            }
+            None => {
+                get_structure(&structure_name).ok_or(ServerFnError::new("Structure not found"))?
+            }
+        };
+        let level = structure
+            .first_level()
+            .ok_or(ServerFnError::new("Structure has no levels"))?;
         let clock_state = ClockState::Paused {
-            remaining: structure.get_level(1).duration(),
+            remaining: structure.get_level(level).duration(),
         };
         let tournament = Tournament {
             created: now(),
             timer_id: timer.timer_id,
             structure_name,
             structure,
-            level: 1,
+            level,
             clock_state,
-            duration_override: None,
+            durations: DurationOverrides::default(),
+            starting_stack: None,
+            entries: EntryRules::default(),
+            payouts: PayoutConfig::default(),
+            players: Players::default(),
+            table_size: None,
+            bounty: None,
+            warnings: Warnings::default(),
+            starts_at,
+            hand_for_hand: None,
+            rotation: 0,
             subscriptions: HashMap::new(),
         };
         tournament.init(timer_id, rx);
@@ -405,8 +908,12 @@ impl Tournament {
     ) {
         // start a thread to do the level changes
         tokio::spawn(async move {
-            // have we given the one minute warning for the break yet
-            let mut gave_warning = false;
+            // the warnings given in this level, by how long before the end
+            // they are
+            let mut given: Vec<Duration> = vec![];
+            // have we told everyone the scheduled start is coming up
+            let mut gave_start_warning = false;
+            let start_warning = Duration::minutes(START_WARNING_MINUTES);
             loop {
                 {
                     let time = match &Timer::get(timer_id).tournament {
@@ -414,21 +921,18 @@ impl Tournament {
                             // the tournament ended
                             break;
                         }
-                        Some(tournament) => {
-                            let r = tournament.clock_state.remaining();
-                            match tournament.structure.get_level(tournament.level) {
-                                Level::Break { .. } => {
-                                    if !gave_warning {
-                                        // wait util < a minute left to give the warning
-                                        r.checked_sub(&Duration::seconds(59))
-                                            .unwrap_or(Duration::zero())
-                                    } else {
-                                        r
-                                    }
-                                }
-                                _ => r,
+                        Some(Tournament {
+                            starts_at: Some(starts_at),
+                            ..
+                        }) => {
+                            let until = starts_at.signed_duration_since(now());
+                            if gave_start_warning {
+                                until
+                            } else {
+                                until - start_warning
                             }
                         }
+                        Some(tournament) => tournament.next_warning_in(&mut given),
                     };
                     // wait until the time has elapsed, or a message that changed the state of the
                     // tournament occurred.
@@ -447,23 +951,28 @@ impl Tournament {
                             // the tournament ended
                             break;
                         }
+                        Some(Tournament {
+                            starts_at: Some(starts_at),
+                            ..
+                        }) => {
+                            let until = starts_at.signed_duration_since(now());
+                            if until <= Duration::zero() {
+                                let _ = timer.record(TimerEvent::Begin);
+                            } else if !gave_start_warning && until <= start_warning {
+                                gave_start_warning = true;
+                                timer.broadcast(None, TournamentMessage::StartingSoon);
+                            }
+                        }
                         Some(tournament) => {
-                            if tournament.clock_state.remaining().num_seconds() <= 0 {
-                                let done = timer.level_up(1);
-                                gave_warning = false;
-                                if done {
+                            let r = tournament.clock_state.remaining();
+                            if r.num_seconds() <= 0 {
+                                let _ = timer.record(TimerEvent::LevelUp);
+                                given.clear();
+                                if timer.tournament.is_none() {
                                     break;
                                 }
-                            } else if let Level::Break { .. } =
-                                tournament.structure.get_level(tournament.level)
-                            {
-                                // maybe give one minute warning
-                                if !gave_warning
-                                    && (tournament.clock_state.remaining() < Duration::minutes(1))
-                                {
-                                    gave_warning = true;
-                                    timer.broadcast(None, TournamentMessage::OneMinuteWarning);
-                                }
+                            } else if let Some(warning) = tournament.due_warning(&mut given) {
+                                timer.broadcast(None, TournamentMessage::Warning(warning));
                             }
                         }
                     }
@@ -474,9 +983,12 @@ impl Tournament {
     }
 
     fn level_up(&mut self, delta: i8) -> LevelUpResult {
-        if self.level as i8 + delta < 0 {
-            return LevelUpResult::Invalid;
-        }
+        let new_level = match self.structure.offset(self.level, delta as isize) {
+            Some(level) => level,
+            // going past the last level ends the tournament
+            None if delta > 0 => return LevelUpResult::Done,
+            None => return LevelUpResult::Invalid,
+        };
 
         // Capture any overtime from the current level before changing levels
         let current_remaining = self.clock_state.remaining();
@@ -486,18 +998,8 @@ impl Tournament {
             Duration::zero()
         };
 
-        self.level = (self.level as i8 + delta) as usize;
-        let level = self.structure.get_level(self.level);
-        if level == &Level::Done {
-            return LevelUpResult::Done;
-        }
-        let mut duration = match level {
-            Level::Break { .. } => level.duration(), // Never override break duration
-            _ => match self.duration_override {
-                Some(duration) => duration,
-                None => level.duration(),
-            },
-        };
+        self.level = new_level;
+        let mut duration = self.level_duration();
 
         // Subtract any overtime from the new level's duration
         duration = duration - overtime;
@@ -519,18 +1021,35 @@ impl Tournament {
         LevelUpResult::Ok
     }
 
-    fn update_settings(&mut self, duration_override: Option<Duration>) {
-        // if the round duration is changing, update the clock_state
-        if duration_override != self.duration_override {
-            self.duration_override = duration_override;
-            let structure = self.structure.get_level(self.level);
-            let duration = if let Level::Break { duration } = structure {
-                duration.clone()
-            } else if let Some(duration) = &duration_override {
-                duration.clone()
-            } else {
-                structure.duration()
-            };
+    /// How long the current level is, after any overrides
+    fn level_duration(&self) -> Duration {
+        let level = self.structure.get_level(self.level);
+        self.durations
+            .duration_for(self.level.number(), level)
+            .unwrap_or(level.duration())
+    }
+
+    fn update_settings(&mut self, settings: TournamentConfig) {
+        let TournamentConfig {
+            durations,
+            starting_stack,
+            entries,
+            payouts,
+            table_size,
+            bounty,
+            warnings,
+        } = settings;
+        self.warnings = warnings;
+        self.starting_stack = starting_stack;
+        self.entries = entries;
+        self.payouts = payouts;
+        self.table_size = table_size;
+        self.bounty = bounty;
+        // if the current level's duration is changing, update the clock_state
+        let old_duration = self.level_duration();
+        self.durations = durations;
+        let duration = self.level_duration();
+        if duration != old_duration {
             match self.clock_state {
                 ClockState::Paused { .. } => {
                     self.clock_state = ClockState::Paused {
@@ -547,13 +1066,110 @@ impl Tournament {
         }
     }
 
+    pub fn settings(&self) -> TournamentConfig {
+        TournamentConfig {
+            durations: self.durations.clone(),
+            starting_stack: self.starting_stack,
+            entries: self.entries.clone(),
+            payouts: self.payouts.clone(),
+            table_size: self.table_size,
+            bounty: self.bounty.clone(),
+            warnings: self.warnings.clone(),
+        }
+    }
+
+    /// The tables with players still in, or just table 1 if the seats
+    /// haven't been drawn
+    fn tables(&self) -> Vec<u32> {
+        let mut tables: Vec<u32> = self
+            .players
+            .all()
+            .iter()
+            .filter(|p| p.busted.is_none())
+            .filter_map(|p| p.table)
+            .collect();
+        tables.sort();
+        tables.dedup();
+        if tables.is_empty() {
+            tables.push(1);
+        }
+        tables
+    }
+
+    /// Keep the hand for hand tables up to date after players bust or move.
+    /// Returns the next hand if the tables left have all finished theirs.
+    fn update_hand_for_hand_tables(&mut self) -> Option<u32> {
+        let tables = self.tables();
+        let hand_for_hand = self.hand_for_hand.as_mut()?;
+        hand_for_hand
+            .set_tables(tables)
+            .then_some(hand_for_hand.hand)
+    }
+
+    /// The warnings for the current level that haven't been given yet.
+    /// Warnings as long as the level itself are never given.
+    fn pending_warnings<'a>(
+        &'a self,
+        given: &'a [Duration],
+    ) -> impl Iterator<Item = &'a LevelWarning> {
+        let level = self.structure.get_level(self.level);
+        let duration = self.level_duration();
+        self.warnings.0.iter().filter(move |w| {
+            w.applies_to(level) && w.before < duration && !given.contains(&w.before)
+        })
+    }
+
+    /// How long until the next warning or the end of the level. The warnings
+    /// in `given` that time added back to the clock put ahead of it again are
+    /// taken out, so they can be given again.
+    fn next_warning_in(&self, given: &mut Vec<Duration>) -> Duration {
+        let r = self.clock_state.remaining();
+        given.retain(|before| *before >= r);
+        self.pending_warnings(given)
+            .map(|w| r - w.before)
+            .fold(r, Duration::min)
+    }
+
+    /// The warning to give now, if one is due, adding it to `given`. When the
+    /// clock jumped past more than one warning, only the last of them is given.
+    fn due_warning(&self, given: &mut Vec<Duration>) -> Option<LevelWarning> {
+        let r = self.clock_state.remaining();
+        let due: Vec<LevelWarning> = self
+            .pending_warnings(given)
+            .filter(|w| w.before >= r)
+            .cloned()
+            .collect();
+        given.extend(due.iter().map(|w| w.before));
+        due.into_iter().min_by_key(|w| w.before)
+    }
+
+    pub fn prize_pool(&self) -> Option<PrizePool> {
+        prize_pool(&self.players, &self.payouts, self.bounty.as_ref())
+    }
+
+    pub fn results(&self) -> Vec<ResultRow> {
+        results(&self.players, &self.payouts, self.bounty.as_ref())
+    }
+
     fn to_roundstate(&self) -> RoundState {
         RoundState {
             timer_id: self.timer_id,
             level: self.level,
             cur: self.structure.get_level(self.level).clone(),
-            next: self.structure.get_level(self.level + 1).clone(),
+            next: self.structure.next_level(self.level).clone(),
             clock: self.clock_state.clone(),
+            entrants: self.players.entrants(),
+            remaining: self.players.remaining(),
+            average_stack: self.players.average_stack(self.starting_stack),
+            last_rebuy_level: self.entries.is_last_rebuy_level(self.level.number()),
+            prize_pool: self.prize_pool(),
+            starts_in: self.starts_at.map(|starts_at| ClockState::Running {
+                remaining: starts_at.signed_duration_since(now()),
+                asof: now(),
+            }),
+            hand_for_hand: self.hand_for_hand.clone(),
+            color_up: self.structure.color_up(self.level),
+            rotation: self.rotation,
         }
     }
 }
@@ -578,39 +1194,89 @@ impl ClockState {
             Self::Running { .. } => *self,
         }
     }
+    /// the same clock with `remaining` left, which can't be less than zero
+    pub(self) fn set_remaining(&self, remaining: Duration) -> ClockState {
+        let remaining = remaining.max(Duration::zero());
+        match self {
+            Self::Paused { .. } => Self::Paused { remaining },
+            Self::Running { .. } => Self::Running {
+                remaining,
+                asof: now(),
+            },
+        }
+    }
 }
 
 pub async fn create_tournament(
     timer_id: Uuid,
     structure_name: String,
+    structure: Option<Structure>,
+    starts_at: Option<DateTime>,
 ) -> Result<(), ServerFnError> {
     // make the timer if it does not exist yet
-    let mut timer = Timer::get_mut(timer_id);
+    let mut timer = Timer::open_mut(timer_id)?;
     if timer.tournament.is_some() {
         return Ok(());
     }
     info!("Creating tournament {timer_id}");
-    timer.make_tournament(structure_name)
+    timer.make_tournament(structure_name, structure, starts_at)
 }
 
-pub fn tourament_settings(timer_id: Uuid) -> Result<Option<Duration>, ServerFnError> {
-    match &Timer::get(timer_id).tournament {
-        Some(t) => Ok(t.duration_override),
+/// Drop the timers that have had no tournament and nobody looking at them
+/// for `timeout`. Their broadcast tasks stop once they are gone.
+fn evict_idle(timeout: std::time::Duration) -> usize {
+    let mut evicted = 0;
+    TIMERS.retain(|_, timer| {
+        if !timer.is_idle() {
+            timer.idle_since = None;
+            return true;
+        }
+        let keep = timer.idle_since.get_or_insert_with(std::time::Instant::now).elapsed() < timeout;
+        if !keep {
+            evicted += 1;
+        }
+        keep
+    });
+    evicted
+}
+
+/// Drop the timers that nobody has used for `timeout`, checking every
+/// minute. Runs forever.
+pub async fn evict_idle_periodically(timeout: std::time::Duration) {
+    loop {
+        sleep(EVICTION_INTERVAL).await;
+        let evicted = evict_idle(timeout);
+        if evicted > 0 {
+            info!("Evicted {evicted} idle timers, {} left", TIMERS.len());
+        }
+    }
+}
+
+pub fn tourament_settings(timer_id: Uuid) -> Result<TournamentConfig, ServerFnError> {
+    let timer = Timer::find(timer_id);
+    match timer.as_ref().and_then(|t| t.tournament.as_ref()) {
+        Some(t) => Ok(t.settings()),
         None => Err(ServerFnError::new("running tournament")),
     }
 }
 
 pub fn set_tournament_settings(
     timer_id: Uuid,
-    duration_override: Option<Duration>,
+    settings: TournamentConfig,
 ) -> Result<(), ServerFnError> {
-    Timer::get_mut(timer_id).update_settings(duration_override);
+    Timer::open_mut(timer_id)?.update_settings(settings);
     Ok(())
 }
 
 pub async fn handle_socket(timer_id: Uuid, device_id: Option<Uuid>, mut socket: WebSocket) {
     let (mut channel, hello) = {
-        let timer = Timer::get(timer_id);
+        let timer = match Timer::open(timer_id) {
+            Ok(timer) => timer,
+            Err(e) => {
+                info!("couldn't open timer {timer_id}: {e}");
+                return;
+            }
+        };
         (
             timer.event_sender.new_receiver(),
             DeviceMessage::NewState(timer.to_timer_comp_state(&device_id)),
@@ -636,11 +1302,33 @@ pub async fn handle_socket(timer_id: Uuid, device_id: Option<Uuid>, mut socket:
                             continue;
                         }
                     }
-                    if let TournamentMessage::OneMinuteWarning = tm {
+                    if let TournamentMessage::Warning(warning) = &tm {
+                        if warning.sound {
+                            let message = JsonSerdeWasmCodec::encode(&DeviceMessage::Beep).expect("Couldn't encode");
+                            if let Err(e) = socket.send(Message::Text(message)).await {
+                                info!("couldn't send {e}");
+                                break;
+                            }
+                        }
+                        continue;
+                    }
+                    if let TournamentMessage::LastRebuyLevel | TournamentMessage::StartingSoon = tm {
                         // this doesn't change the state, only gives a notification elsewhere
                         continue;
                     }
-                    if let TournamentMessage::LevelUp(_) = tm {
+                    if let TournamentMessage::Seating(change) = tm {
+                        // the players changing seats comes in its own message
+                        let message = JsonSerdeWasmCodec::encode(&DeviceMessage::Seating(change)).expect("Couldn't encode");
+                        if let Err(e) = socket.send(Message::Text(message)).await {
+                            info!("couldn't send {e}");
+                            break;
+                        }
+                        continue;
+                    }
+                    if let TournamentMessage::LevelUp(_)
+                    | TournamentMessage::Begin
+                    | TournamentMessage::NextHand(_) = tm
+                    {
                         let message = JsonSerdeWasmCodec::encode(&DeviceMessage::Beep).expect("Couldn't encode");
                         if let Err(e) = socket.send(Message::Text(message)).await {
                             info!("couldn't send {e}");
@@ -666,7 +1354,9 @@ pub async fn handle_socket(timer_id: Uuid, device_id: Option<Uuid>, mut socket:
                 Some(Ok(Message::Text(msg))) => {
                     match serde_json::from_str::<Command>(&msg) {
                         Ok(cmd) =>  {
-                            Timer::get_mut(timer_id).execute( &cmd, device_id);
+                            if let Err(e) = Timer::get_mut(timer_id).execute( &cmd, device_id) {
+                                info!("couldn't execute {cmd:?}: {e}");
+                            }
                         },
 
                         Err(_) => break
@@ -702,3 +1392,344 @@ pub async fn handle_socket(timer_id: Uuid, device_id: Option<Uuid>, mut socket:
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::{get_structure, structure_names};
+    use proptest::prelude::*;
+
+    fn tournament(structure_name: &str) -> Tournament {
+        let structure = get_structure(structure_name).unwrap();
+        Tournament {
+            created: now(),
+            timer_id: Uuid::new_v4(),
+            structure_name: structure_name.to_string(),
+            level: structure.first_level().unwrap(),
+            clock_state: ClockState::Paused {
+                remaining: structure.levels[0].duration(),
+            },
+            structure,
+            durations: DurationOverrides::default(),
+            starting_stack: None,
+            entries: EntryRules::default(),
+            payouts: PayoutConfig::default(),
+            players: Players::default(),
+            table_size: None,
+            bounty: None,
+            warnings: Warnings::default(),
+            starts_at: None,
+            hand_for_hand: None,
+            rotation: 0,
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn idle_timers_are_evicted() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let idle = Uuid::new_v4();
+            let watched = Uuid::new_v4();
+            Timer::get(idle);
+            let screen = Timer::get(watched).event_sender.new_receiver();
+            // looking doesn't make one
+            let unknown = Uuid::new_v4();
+            assert!(Timer::find(unknown).is_none());
+            assert!(tourament_settings(unknown).is_err());
+            assert!(!TIMERS.contains_key(&unknown));
+            evict_idle(std::time::Duration::ZERO);
+            assert!(!TIMERS.contains_key(&idle));
+            assert!(TIMERS.contains_key(&watched));
+
+            // once the screen is gone, so is the timer
+            drop(screen);
+            evict_idle(std::time::Duration::ZERO);
+            assert!(!TIMERS.contains_key(&watched));
+
+            // a full registry still has the timers it already has
+            let kept = Uuid::new_v4();
+            let _screen = Timer::get(kept).event_sender.new_receiver();
+            assert!(Timer::open_within(Uuid::new_v4(), 0).is_err());
+            assert!(Timer::open_within(kept, 0).is_ok());
+        });
+    }
+
+    #[test]
+    fn the_last_rebuy_level_is_only_announced_going_forward() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut timer = Timer::get_mut(Uuid::new_v4());
+            let mut t = tournament("Nightly NLHE");
+            t.entries.rebuy = Some(EntryRule {
+                price: 20,
+                chips: 1000,
+                last_level: Some(2),
+            });
+            timer.tournament = Some(t);
+            let mut screen = timer.event_sender.new_receiver();
+            let mut announced = || {
+                let mut last_rebuy = false;
+                while let Ok((message, _)) = screen.try_recv() {
+                    last_rebuy |= matches!(message, TournamentMessage::LastRebuyLevel);
+                }
+                last_rebuy
+            };
+            timer.level_up(1);
+            assert!(announced());
+            timer.level_up(1);
+            assert!(!announced());
+            timer.level_up(-1);
+            assert_eq!(timer.tournament.as_ref().unwrap().level.number(), 2);
+            assert!(!announced());
+        });
+    }
+
+    #[test]
+    fn changing_the_time_keeps_the_level() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut timer = Timer::get_mut(Uuid::new_v4());
+            // what `execute` does, without logging it
+            let run = |timer: &mut Timer, command: Command| {
+                timer.apply(&LogEntry::new(TimerEvent::Command {
+                    command,
+                    device_id: None,
+                }))
+            };
+            assert!(run(&mut timer, Command::SetTime(Duration::minutes(5))).is_err());
+            let mut t = tournament("Nightly NLHE");
+            t.level_up(1);
+            timer.tournament = Some(t);
+            let mut screen = timer.event_sender.new_receiver();
+            let remaining = |timer: &Timer| timer.tournament.as_ref().unwrap().clock_state;
+
+            run(&mut timer, Command::SetTime(Duration::minutes(5))).unwrap();
+            assert_eq!(remaining(&timer), ClockState::Paused { remaining: Duration::minutes(5) });
+            assert!(matches!(screen.try_recv(), Ok((TournamentMessage::TimeChanged, None))));
+            run(&mut timer, Command::AddTime(Duration::minutes(1))).unwrap();
+            assert_eq!(remaining(&timer).remaining(), Duration::minutes(6));
+            // there's never less than nothing left
+            run(&mut timer, Command::AddTime(Duration::minutes(-10))).unwrap();
+            assert_eq!(remaining(&timer).remaining(), Duration::zero());
+            // or more than the clock can count
+            run(&mut timer, Command::SetTime(Duration::MAX)).unwrap();
+            assert!(run(&mut timer, Command::AddTime(Duration::MAX)).is_err());
+            assert_eq!(remaining(&timer).remaining(), Duration::MAX);
+
+            run(&mut timer, Command::Resume).unwrap();
+            run(&mut timer, Command::SetTime(Duration::minutes(1))).unwrap();
+            let running = remaining(&timer);
+            assert!(running.remaining() <= Duration::minutes(1));
+            assert!(running.remaining() > Duration::seconds(59));
+            assert!(!running.is_paused());
+            assert_eq!(timer.tournament.as_ref().unwrap().level.number(), 2);
+        });
+    }
+
+    #[test]
+    fn a_scheduled_start_counts_down() {
+        let mut t = tournament("Nightly NLHE");
+        assert_eq!(t.to_roundstate().starts_in, None);
+        t.starts_at = Some(now() + Duration::minutes(10));
+        let starts_in = t.to_roundstate().starts_in.unwrap();
+        assert!(!starts_in.is_paused());
+        assert!(starts_in.remaining() <= Duration::minutes(10));
+        assert!(starts_in.remaining() > Duration::minutes(9));
+        // the level clock waits for the start
+        assert!(t.clock_state.is_paused());
+    }
+
+    #[test]
+    fn warnings_are_given_before_the_level_ends() {
+        let text = "5m push: Five minutes\n10s sound: Ten seconds\n1m30s breaks sound push: Back soon";
+        let warnings = Warnings::parse(text).unwrap();
+        assert_eq!(warnings.to_string(), text);
+        assert_eq!(Warnings::parse(&Warnings::default().to_string()), Ok(Warnings::default()));
+        for bad in ["5m", "5 push: x", "0s: x", "5m loud: x", "-1m: x", "5m:"] {
+            assert!(Warnings::parse(bad).is_err(), "{bad}");
+        }
+
+        let mut t = tournament("Nightly NLHE");
+        t.warnings = warnings;
+        let pending = |t: &Tournament, given: &[Duration]| -> Vec<Duration> {
+            t.pending_warnings(given).map(|w| w.before).collect()
+        };
+        assert!(!matches!(t.structure.get_level(t.level), Level::Break { .. }));
+        assert_eq!(pending(&t, &[]), vec![Duration::minutes(5), Duration::seconds(10)]);
+        assert_eq!(pending(&t, &[Duration::minutes(5)]), vec![Duration::seconds(10)]);
+        // a warning as long as the level is never given
+        t.warnings.0[0].before = t.level_duration();
+        assert_eq!(pending(&t, &[]), vec![Duration::seconds(10)]);
+    }
+
+    #[test]
+    fn a_warning_is_given_once_until_time_is_added_back() {
+        let mut t = tournament("Nightly NLHE");
+        t.warnings = Warnings::parse("5m push: Five minutes\n1m push: One minute").unwrap();
+        let mut given = vec![];
+        let at = |t: &mut Tournament, remaining: i64, given: &mut Vec<Duration>| {
+            t.clock_state = ClockState::Paused {
+                remaining: Duration::minutes(remaining),
+            };
+            let next = t.next_warning_in(given);
+            (next, t.due_warning(given).map(|w| w.before))
+        };
+        assert_eq!(at(&mut t, 10, &mut given), (Duration::minutes(5), None));
+        assert_eq!(at(&mut t, 5, &mut given), (Duration::zero(), Some(Duration::minutes(5))));
+        // once it's given the clock waits for the next one, paused or not
+        for _ in 0..3 {
+            assert_eq!(at(&mut t, 5, &mut given), (Duration::minutes(4), None));
+        }
+        assert_eq!(at(&mut t, 3, &mut given), (Duration::minutes(2), None));
+        // adding time back puts it ahead of the clock again
+        assert_eq!(at(&mut t, 7, &mut given), (Duration::minutes(2), None));
+        assert_eq!(at(&mut t, 5, &mut given), (Duration::zero(), Some(Duration::minutes(5))));
+        // jumping past both only gives the last
+        assert_eq!(at(&mut t, 0, &mut vec![]).1, Some(Duration::minutes(1)));
+    }
+
+    #[test]
+    fn hand_for_hand_waits_for_every_table() {
+        let mut t = tournament("Nightly NLHE");
+        assert_eq!(t.tables(), vec![1]);
+        let ids: Vec<Uuid> = (1..=3)
+            .map(|table| {
+                let id = t.players.register(&format!("Player {table}"), None).unwrap();
+                t.players.set_seat(id, Seat { table, seat: 1 });
+                id
+            })
+            .collect();
+        let mut h = HandForHand::new(t.tables());
+        assert!(h.hand_complete(4).is_err());
+        assert_eq!(h.hand_complete(1), Ok(false));
+        assert_eq!(h.hand_complete(1), Ok(false));
+        assert_eq!(h.waiting_on(), vec![2, 3]);
+        assert_eq!(h.hand_complete(3), Ok(false));
+        assert_eq!(h.hand_complete(2), Ok(true));
+        assert_eq!((h.hand, h.waiting_on()), (2, vec![1, 2, 3]));
+
+        // the table still playing busts out, so the others are done
+        h.hand_complete(1).unwrap();
+        h.hand_complete(3).unwrap();
+        t.hand_for_hand = Some(h);
+        t.players.bust(ids[1]).unwrap();
+        assert_eq!(t.update_hand_for_hand_tables(), Some(3));
+        assert_eq!(t.hand_for_hand.unwrap().tables, vec![1, 3]);
+    }
+
+    #[test]
+    fn hand_for_hand_puts_the_clock_back() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut timer = Timer::get_mut(Uuid::new_v4());
+            timer.tournament = Some(tournament("Nightly NLHE"));
+            let paused = |timer: &Timer| timer.tournament.as_ref().unwrap().clock_state.is_paused();
+            for running in [true, false] {
+                if running {
+                    timer.run_command(&Command::Resume, None).unwrap();
+                }
+                timer.run_command(&Command::StartHandForHand, None).unwrap();
+                assert!(paused(&timer));
+                timer.run_command(&Command::StopHandForHand, None).unwrap();
+                assert_eq!(paused(&timer), !running);
+                timer.run_command(&Command::Pause, None).unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn durations_can_be_changed_by_level() {
+        let durations = DurationOverrides::parse("3+: 12\nbreaks: 15, 5: 30").unwrap();
+        assert_eq!(durations.to_string(), "3+: 12\nbreaks: 15\n5: 30");
+        assert_eq!(DurationOverrides::parse(&durations.to_string()), Ok(durations.clone()));
+        for bad in ["3+", "x: 5", "0: 5", "5-3: 10", "3: 0", "3: -5", "all: 999999999999999"] {
+            assert!(DurationOverrides::parse(bad).is_err(), "{bad}");
+        }
+
+        let mut t = tournament("Nightly NLHE");
+        let before = t.level_duration();
+        t.update_settings(TournamentConfig {
+            durations,
+            ..t.settings()
+        });
+        // the first two levels keep their time
+        assert_eq!(t.level_duration(), before);
+        assert_eq!(t.clock_state.remaining(), before);
+        for _ in 1..t.structure.levels.len() {
+            t.level_up(1);
+            let expected = match (t.level.number(), t.structure.get_level(t.level)) {
+                (_, Level::Break { .. }) => Duration::minutes(15),
+                (1..=2, level) => level.duration(),
+                (5, _) => Duration::minutes(30),
+                _ => Duration::minutes(12),
+            };
+            assert_eq!(t.clock_state.remaining(), expected, "level {}", t.level);
+        }
+    }
+
+    #[test]
+    fn prev_level_at_the_start_is_invalid() {
+        for name in structure_names() {
+            let mut t = tournament(&name);
+            assert!(matches!(t.level_up(-1), LevelUpResult::Invalid), "{name}");
+            assert_eq!(t.level.number(), 1, "{name}");
+        }
+    }
+
+    #[test]
+    fn the_last_level_is_played() {
+        for name in structure_names() {
+            let mut t = tournament(&name);
+            let last = t.structure.levels.len();
+            for _ in 1..last {
+                assert!(matches!(t.level_up(1), LevelUpResult::Ok), "{name}");
+            }
+            assert_eq!(t.level.number(), last, "{name}");
+            assert_eq!(t.to_roundstate().cur, t.structure.levels[last - 1], "{name}");
+            assert_eq!(t.to_roundstate().next, Level::Done, "{name}");
+            assert!(matches!(t.level_up(1), LevelUpResult::Done), "{name}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn level_up_never_leaves_the_structure(
+            name in prop::sample::select(structure_names()),
+            steps in prop::collection::vec(prop::bool::ANY, 0..200),
+        ) {
+            let mut t = tournament(&name);
+            let len = t.structure.levels.len();
+            for forward in steps {
+                let before = t.level.number();
+                match t.level_up(if forward { 1 } else { -1 }) {
+                    LevelUpResult::Ok => {
+                        let expected = if forward { before + 1 } else { before - 1 };
+                        prop_assert_eq!(t.level.number(), expected);
+                    }
+                    LevelUpResult::Invalid => {
+                        prop_assert!(!forward && before == 1);
+                        prop_assert_eq!(t.level.number(), 1);
+                    }
+                    LevelUpResult::Done => {
+                        prop_assert!(forward && before == len);
+                        break;
+                    }
+                }
+                prop_assert_ne!(&t.to_roundstate().cur, &Level::Done);
+            }
+        }
+    }
+}